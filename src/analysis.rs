@@ -0,0 +1,427 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing analyses of a benchmark's history of runs, such as finding
+// the runs where performance stepped up or down.
+
+use serde::Serialize;
+
+use crate::compare::Change;
+use crate::metric::Metric;
+use crate::types::{BenchResult, IndividualBenchInfo};
+
+// A step needs this many runs either side of it, so a single odd run isn't taken
+// as two steps, one there and one back.
+pub const MIN_SEGMENT: usize = 2;
+
+// How many runs either side of a run its rolling median is taken over
+pub const ROLLING_WINDOW: usize = 5;
+
+// Runs are suspicious when noisier than this many times the benchmark's noise band
+pub const OUTLIER_FACTOR: f64 = 3.0;
+
+// How noisy a run looks, each as the median over the run's benchmarks so one
+// flaky benchmark doesn't condemn the whole run
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RunNoise {
+    // Coefficient of variation of the real time over repetitions, None without any
+    pub cv_percent:        Option<f64>,
+    // Difference from the median real time of the runs before or after it, None without any
+    pub deviation_percent: Option<f64>
+}
+
+impl RunNoise {
+    pub fn is_suspicious(&self, threshold_percent: f64) -> bool {
+        self.cv_percent.is_some_and(|cv| cv > threshold_percent)
+            || self.deviation_percent.is_some_and(|deviation| deviation.abs() > threshold_percent)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ChangePoint {
+    // The first run after the step
+    pub run:          usize,
+    // The run before it, whose source hash the responsible changes are after
+    pub previous_run: usize,
+    // Medians of the values between this step and its neighbours
+    pub before:       f64,
+    pub after:        f64,
+    pub percent_diff: f64,
+    pub change:       Change
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SeriesAnalysis {
+    pub name:          String,
+    pub metric:        Metric,
+    pub unit:          String,
+    // How many runs had this benchmark
+    pub runs:          usize,
+    pub change_points: Vec<ChangePoint>
+}
+
+pub(crate) fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    match values.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => values[n / 2],
+        n => (values[n / 2 - 1] + values[n / 2]) / 2.0
+    }
+}
+
+// The run to run noise, from the median absolute difference between neighbours,
+// which a few steps barely move. Floored so a perfectly flat series still works.
+fn noise_sigma(values: &[f64]) -> f64 {
+    let mut diffs: Vec<f64> = values.windows(2).map(|pair| (pair[1] - pair[0]).abs()).collect();
+    let sigma = median(&mut diffs) / (0.6745 * std::f64::consts::SQRT_2);
+    let mut magnitudes: Vec<f64> = values.iter().map(|value| value.abs()).collect();
+    sigma.max(median(&mut magnitudes) * 1e-3).max(f64::MIN_POSITIVE)
+}
+
+// Find where the level of a series changes, as the segmentation minimising the
+// absolute deviation from each segment's median plus a BIC penalty per step.
+// Absolute rather than squared deviations keep a single odd run from outweighing
+// the rest. Returns the index each new segment starts at.
+//
+// This is solved exactly by PELT: each possible step is extended over the runs
+// after it, keeping the segment's deviation up to date as each run is added, and
+// dropped once it can no longer beat a later step. That's close to linear for a
+// history with regular steps, and O(n^2 log n) at worst, where sorting each of
+// the O(n^2) segments would be O(n^3 log n).
+pub fn detect(values: &[f64], min_segment: usize) -> Vec<usize> {
+    let n = values.len();
+    let min_segment = min_segment.max(1);
+    if n < 2 * min_segment {
+        return Vec::new();
+    }
+
+    // Laplace noise, with its scale estimated from the run to run differences
+    let scale = noise_sigma(values) / std::f64::consts::SQRT_2;
+    let penalty = 2.0 * (n as f64).ln();
+
+    // best[t] is the cheapest segmentation of the first t values, with its last step at last[t]
+    let mut best = vec![f64::INFINITY; n + 1];
+    let mut last = vec![0; n + 1];
+    best[0] = -penalty;
+    for s in (0..=n - min_segment).filter(|s| *s == 0 || *s >= min_segment) {
+        let mut segment = Deviation::default();
+        let mut stop = n;
+        for t in s + 1..=n {
+            segment.add(values[t - 1]);
+            if t - s < min_segment {
+                continue;
+            }
+
+            let cost = best[s] + segment.sum() / scale;
+            if cost + penalty < best[t] {
+                best[t] = cost + penalty;
+                last[t] = s;
+            }
+            // Splitting a segment never costs more, so once a step at t beats one at s,
+            // s can't be the last step of anything t could be followed by
+            if stop == n && t >= min_segment && cost > best[t] {
+                stop = (t + min_segment - 1).min(n);
+            }
+            if t >= stop {
+                break;
+            }
+        }
+    }
+
+    let mut steps = Vec::new();
+    let mut t = n;
+    while last[t] > 0 {
+        t = last[t];
+        steps.push(t);
+    }
+    steps.reverse();
+    steps
+}
+
+// The sum of absolute deviations from the median of the values added so far,
+// keeping the lower half in a max heap and the upper half in a min heap
+#[derive(Default)]
+struct Deviation {
+    lower:     std::collections::BinaryHeap<Ordered>,
+    upper:     std::collections::BinaryHeap<std::cmp::Reverse<Ordered>>,
+    lower_sum: f64,
+    upper_sum: f64
+}
+
+impl Deviation {
+    fn add(&mut self, value: f64) {
+        if self.lower.peek().is_none_or(|top| value <= top.0) {
+            self.lower.push(Ordered(value));
+            self.lower_sum += value;
+        } else {
+            self.upper.push(std::cmp::Reverse(Ordered(value)));
+            self.upper_sum += value;
+        }
+
+        // The lower half holds the median, and at most one more value than the upper
+        if self.lower.len() > self.upper.len() + 1 {
+            let moved = self.lower.pop().map_or(0.0, |top| top.0);
+            self.lower_sum -= moved;
+            self.upper.push(std::cmp::Reverse(Ordered(moved)));
+            self.upper_sum += moved;
+        } else if self.upper.len() > self.lower.len() {
+            let moved = self.upper.pop().map_or(0.0, |top| (top.0).0);
+            self.upper_sum -= moved;
+            self.lower.push(Ordered(moved));
+            self.lower_sum += moved;
+        }
+    }
+
+    // Each value in the upper half is its distance above the median, and each in
+    // the lower its distance below, leaving the median itself over when odd
+    fn sum(&self) -> f64 {
+        let middle = if self.lower.len() > self.upper.len() { self.lower.peek().map_or(0.0, |top| top.0) } else { 0.0 };
+        (self.upper_sum - self.lower_sum + middle).max(0.0)
+    }
+}
+
+#[derive(PartialEq)]
+struct Ordered(f64);
+
+impl Eq for Ordered {}
+
+impl PartialOrd for Ordered {
+    fn partial_cmp(&self, other: &Ordered) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ordered {
+    fn cmp(&self, other: &Ordered) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// Change points in one series of values, dropping steps within the noise band.
+// Each value is paired with the run it came from.
+fn change_points(series: &[(usize, f64)], higher_is_better: bool, noise_percent: f64) -> Vec<ChangePoint> {
+    let values: Vec<f64> = series.iter().map(|(_, value)| *value).collect();
+    let mut bounds = vec![0];
+    bounds.extend(detect(&values, MIN_SEGMENT));
+    bounds.push(values.len());
+
+    let level = |s: usize, t: usize| median(&mut values[s..t].to_vec());
+    bounds
+        .windows(3)
+        .filter_map(|bound| {
+            let (before, after) = (level(bound[0], bound[1]), level(bound[1], bound[2]));
+            let percent_diff = 100.0 * (after - before) / before;
+            if percent_diff.is_nan() || percent_diff.abs() < noise_percent {
+                return None;
+            }
+
+            let change = if (after > before) != higher_is_better { Change::Regression } else { Change::Improvement };
+            let (run, previous_run) = (series[bound[1]].0, series[bound[1] - 1].0);
+            Some(ChangePoint { run, previous_run, before, after, percent_diff, change })
+        })
+        .collect()
+}
+
+// Find the change points in each benchmark's history of each metric, in the order
+// benchmarks first appear. Runs without a benchmark, or excluded, are left out of its series.
+pub fn analyze(info: &IndividualBenchInfo, metrics: &[Metric], noise_percent: f64) -> Vec<SeriesAnalysis> {
+    let mut names: Vec<&str> = Vec::new();
+    for result in info.benchmarks.iter().flatten() {
+        if !names.contains(&result.name.as_str()) {
+            names.push(&result.name);
+        }
+    }
+
+    let mut analyses = Vec::new();
+    for name in names {
+        for metric in metrics {
+            let mut unit = None;
+            let mut series = Vec::new();
+            for (run, results) in info.benchmarks.iter().enumerate().filter(|(run, _)| !info.is_excluded(*run)) {
+                if let Some(result) = results.iter().find(|result| result.name == name) {
+                    if let Some(value) = metric.value(result) {
+                        unit.get_or_insert_with(|| metric.unit(result));
+                        series.push((run, value));
+                    }
+                }
+            }
+
+            if let Some(unit) = unit {
+                analyses.push(SeriesAnalysis {
+                    name: name.to_string(),
+                    metric: metric.clone(),
+                    unit,
+                    runs: series.len(),
+                    change_points: change_points(&series, metric.higher_is_better(), noise_percent)
+                });
+            }
+        }
+    }
+
+    analyses
+}
+
+// Real times of each benchmark in a run, several when it was run with repetitions.
+// Aggregates google/benchmark adds for those, such as BM_Foo_mean, are skipped.
+fn repetitions(results: &[BenchResult]) -> Vec<(&str, Vec<f64>)> {
+    let mut repetitions: Vec<(&str, Vec<f64>)> = Vec::new();
+    for result in results.iter().filter(|result| result.aggregate_name().is_none()) {
+        match repetitions.iter_mut().find(|(name, _)| *name == result.name) {
+            Some((_, times)) => times.push(result.real_time),
+            None => repetitions.push((&result.name, vec![result.real_time]))
+        }
+    }
+
+    repetitions
+}
+
+fn coefficient_of_variation(times: &[f64]) -> Option<f64> {
+    if times.len() < 2 {
+        return None;
+    }
+
+    let mean = times.iter().sum::<f64>() / times.len() as f64;
+    let variance = times.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / (times.len() - 1) as f64;
+    Some(100.0 * variance.sqrt() / mean).filter(|cv| cv.is_finite())
+}
+
+// The noise of every run, by index. Excluded runs get theirs too, but aren't part of
+// the rolling medians any run is compared with.
+pub fn run_noise(info: &IndividualBenchInfo) -> Vec<RunNoise> {
+    let levels: Vec<Vec<(&str, f64)>> = info
+        .benchmarks
+        .iter()
+        .map(|results| repetitions(results).into_iter().map(|(name, mut times)| (name, median(&mut times))).collect())
+        .collect();
+    let included: Vec<usize> = (0..levels.len()).filter(|run| !info.is_excluded(*run)).collect();
+
+    (0..levels.len())
+        .map(|run| {
+            let split = included.partition_point(|other| *other < run);
+            let before: Vec<usize> = included[split.saturating_sub(ROLLING_WINDOW)..split].to_vec();
+            let after: Vec<usize> = included[split..].iter().filter(|other| **other != run).take(ROLLING_WINDOW).cloned().collect();
+
+            // Compared with whichever side is closer, so the runs either side of a genuine
+            // step in performance aren't mistaken for outliers
+            let deviation = |name: &str, level: f64, side: &[usize]| {
+                let mut around: Vec<f64> = side
+                    .iter()
+                    .filter_map(|other| levels[*other].iter().find(|(other_name, _)| *other_name == name))
+                    .map(|(_, level)| *level)
+                    .collect();
+                let typical = Some(median(&mut around)).filter(|_| around.len() >= 2)?;
+                Some(100.0 * (level - typical) / typical).filter(|deviation| deviation.is_finite())
+            };
+            let mut deviations: Vec<f64> = levels[run]
+                .iter()
+                .filter_map(|(name, level)| {
+                    let sides = [deviation(name, *level, &before), deviation(name, *level, &after)];
+                    sides.iter().flatten().cloned().min_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap_or(std::cmp::Ordering::Equal))
+                })
+                .collect();
+            let mut cvs: Vec<f64> =
+                repetitions(&info.benchmarks[run]).iter().filter_map(|(_, times)| coefficient_of_variation(times)).collect();
+
+            RunNoise {
+                cv_percent:        Some(median(&mut cvs)).filter(|_| !cvs.is_empty()),
+                deviation_percent: Some(median(&mut deviations)).filter(|_| !deviations.is_empty())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{analyze, detect, run_noise, MIN_SEGMENT};
+    use crate::compare::Change;
+    use crate::metric::Metric;
+    use crate::types::{BenchResult, IndividualBenchInfo};
+
+    #[test]
+    fn check_detect() {
+        let noisy = |level: f64, count: usize| -> Vec<f64> {
+            (0..count).map(|i| level + [0.3, -0.2, 0.1, -0.4, 0.2][i % 5]).collect()
+        };
+
+        let flat = noisy(100.0, 20);
+        assert!(detect(&flat, MIN_SEGMENT).is_empty());
+
+        let mut stepped = noisy(100.0, 12);
+        stepped.extend(noisy(120.0, 8));
+        stepped.extend(noisy(90.0, 10));
+        assert_eq!(detect(&stepped, MIN_SEGMENT), vec![12, 20]);
+
+        // A single odd run is an outlier, not two steps
+        let mut spike = noisy(100.0, 20);
+        spike[10] = 150.0;
+        assert!(detect(&spike, MIN_SEGMENT).is_empty());
+
+        assert!(detect(&[1.0, 2.0, 3.0], MIN_SEGMENT).is_empty());
+        assert_eq!(detect(&[1.0, 1.0, 1.0, 5.0, 5.0, 5.0], MIN_SEGMENT), vec![3]);
+
+        // Long histories stay quick to segment
+        let mut long = noisy(100.0, 3000);
+        long.extend(noisy(80.0, 3000));
+        assert_eq!(detect(&long, MIN_SEGMENT), vec![3000]);
+    }
+
+    #[test]
+    fn check_analyze() {
+        let result = BenchResult::timed;
+
+        // BM_B is missing from run 2 and steps by less than the noise band
+        let benchmarks = (0..10)
+            .map(|run| {
+                let a = if run < 6 { 10.0 } else { 8.0 };
+                let b = if run < 5 { 50.0 } else { 50.2 };
+                if run == 2 {
+                    vec![result("BM_A", a)]
+                } else {
+                    vec![result("BM_A", a), result("BM_B", b)]
+                }
+            })
+            .collect();
+        let info = IndividualBenchInfo { benchmarks, ..Default::default() };
+
+        let analyses = analyze(&info, &[Metric::RealTime], 1.0);
+        assert_eq!(analyses.len(), 2);
+        assert_eq!(analyses[0].change_points.len(), 1);
+        let step = &analyses[0].change_points[0];
+        assert_eq!((step.run, step.previous_run, step.change), (6, 5, Change::Improvement));
+        assert_eq!(step.percent_diff, -20.0);
+        assert_eq!(analyses[1].runs, 9);
+        assert!(analyses[1].change_points.is_empty());
+    }
+
+    #[test]
+    fn check_run_noise() {
+        let result = BenchResult::timed;
+        let mean = BenchResult {
+            extra: vec![("aggregate_name".to_string(), serde_json::json!("mean"))].into_iter().collect(),
+            ..result("BM_A_mean", 1000.0)
+        };
+
+        // Run 3 was throttled, and run 5 had noisy repetitions
+        let mut benchmarks: Vec<Vec<BenchResult>> =
+            (0..8).map(|run| vec![result("BM_A", 10.0 + (run % 2) as f64 * 0.1), result("BM_B", 20.0)]).collect();
+        benchmarks[3] = vec![result("BM_A", 13.0), result("BM_B", 26.0)];
+        benchmarks[5] = vec![result("BM_A", 8.0), result("BM_A", 12.0), mean, result("BM_B", 20.0)];
+        let mut info = IndividualBenchInfo { benchmarks, tags: vec![Default::default(); 8], ..Default::default() };
+
+        let noise = run_noise(&info);
+        assert_eq!(noise[0].cv_percent, None);
+        assert!((noise[3].deviation_percent.unwrap() - 30.0).abs() < 1.0);
+        assert!(noise[4].deviation_percent.unwrap().abs() < 1.0);
+        assert!((noise[5].cv_percent.unwrap() - 28.28).abs() < 0.01);
+        let suspicious: Vec<usize> = (0..8).filter(|run| noise[*run].is_suspicious(3.0)).collect();
+        assert_eq!(suspicious, vec![3, 5]);
+
+        // Excluded runs are left out of analyses
+        info.benchmarks = (0..8).map(|run| vec![result("BM_A", if run < 4 { 10.0 } else { 12.0 })]).collect();
+        assert_eq!(analyze(&info, &[Metric::RealTime], 1.0)[0].change_points.len(), 1);
+        for run in 4..8 {
+            info.tags[run].insert(crate::types::EXCLUDED_TAG.to_string(), "true".to_string());
+        }
+        assert!(analyze(&info, &[Metric::RealTime], 1.0)[0].change_points.is_empty());
+        assert!(info.is_excluded(4) && !info.is_excluded(3));
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing functionality for fingerprinting the executables
+// that produce our benchmark runs, so that two runs can be traced back
+// to (possibly) different builds.
+
+use std::fs;
+use std::path;
+use std::process;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::types::BinaryInfo;
+
+#[derive(Deserialize)]
+struct CompileCommand {
+    command:   Option<String>,
+    arguments: Option<Vec<String>>,
+    // The object file written, which newer CMake lists explicitly
+    output:    Option<String>
+}
+
+impl CompileCommand {
+    fn args(&self) -> Option<Vec<String>> {
+        match (&self.arguments, &self.command) {
+            (Some(arguments), _) => Some(arguments.clone()),
+            (None, Some(command)) => Some(command.split_whitespace().map(|s| s.to_string()).collect()),
+            (None, None) => None
+        }
+    }
+
+    // Whether this compiles part of target: CMake puts a target's objects under
+    // CMakeFiles/<target>.dir, otherwise a foo.o is taken to be part of a binary foo.
+    fn builds(&self, target: &str) -> bool {
+        let args = self.args().unwrap_or_default();
+        let output = self.output.clone().or_else(|| args.iter().skip_while(|arg| *arg != "-o").nth(1).cloned());
+        let output = match output {
+            Some(output) => output,
+            None => return false
+        };
+
+        let object = path::Path::new(&output);
+        let dir = format!("{}.dir", target);
+        object.components().any(|component| component.as_os_str() == dir.as_str())
+            || object.file_stem().is_some_and(|stem| stem == target)
+    }
+}
+
+pub fn fingerprint(source_bin: &str, source_root: &str) -> Option<BinaryInfo> {
+    let metadata = fs::metadata(source_bin).ok()?;
+    let mtime = match metadata.modified().map(|t| t.duration_since(std::time::UNIX_EPOCH)) {
+        Ok(Ok(since_epoch)) => since_epoch.as_secs(),
+        _ => 0
+    };
+
+    let (compiler, flags) = match compile_command(source_bin, source_root) {
+        Some((compiler, flags)) => (Some(compiler), Some(flags)),
+        None => (None, None)
+    };
+
+    Some(BinaryInfo { size: metadata.len(), mtime, sha256: sha256(source_bin)?, build_id: build_id(source_bin), compiler, flags })
+}
+
+pub fn sha256(file: &str) -> Option<String> {
+    let contents = fs::read(file).ok()?;
+    let digest = Sha256::digest(&contents);
+    Some(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// ELF binaries linked with --build-id carry a note we can read back; other
+// platforms (or a missing readelf) simply don't get one.
+pub fn build_id(file: &str) -> Option<String> {
+    let output = process::Command::new("readelf").arg("--notes").arg(file).output().ok()?;
+    let raw: String = String::from_utf8_lossy(&output.stdout).to_string();
+    raw.lines().find_map(|line| line.trim().strip_prefix("Build ID:")).map(|id| id.trim().to_string())
+}
+
+// CMake writes compile_commands.json to the root of the build tree, so look for
+// one alongside the binary and in its parents up to the build tree (the directory
+// with the CMakeCache.txt) or the source root. Only a binary outside any build tree
+// falls back to the source root's, as with several build trees (Release and Debug,
+// say) that could be another one's flags.
+fn find_compile_commands(source_bin: &str, source_root: &str) -> Option<path::PathBuf> {
+    let root = path::Path::new(source_root);
+    let mut candidates = Vec::new();
+    let mut in_build_tree = false;
+    for dir in path::Path::new(source_bin).ancestors().skip(1) {
+        candidates.push(dir.join("compile_commands.json"));
+        in_build_tree = dir.join("CMakeCache.txt").is_file();
+        if in_build_tree || dir == root {
+            break;
+        }
+    }
+
+    if !in_build_tree {
+        candidates.extend(vec![root.join("compile_commands.json"), root.join("build").join("compile_commands.json")]);
+    }
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+// The command compiling part of the binary, or None rather than a guess when no
+// entry can be tied to it.
+fn compile_command(source_bin: &str, source_root: &str) -> Option<(String, String)> {
+    let contents = fs::read(find_compile_commands(source_bin, source_root)?).ok()?;
+    let commands: Vec<CompileCommand> = serde_json::from_slice(&contents).ok()?;
+    let target = path::Path::new(source_bin).file_stem()?.to_str()?;
+    let command = commands.into_iter().find(|command| command.builds(target))?;
+    split_compile_command(&command.args()?)
+}
+
+// Reduce a compile command to the compiler and the flags that affect codegen,
+// dropping the per-file bits (inputs, outputs, include paths).
+pub fn split_compile_command(args: &[String]) -> Option<(String, String)> {
+    let (compiler, rest) = args.split_first()?;
+    let mut flags: Vec<&str> = vec![];
+    let mut skip_next = false;
+    for arg in rest {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+
+        match arg.as_str() {
+            "-o" | "-I" | "-isystem" | "-MF" | "-MT" | "-MQ" => skip_next = true,
+            "-c" | "-MD" | "-MMD" => {}
+            a if a.starts_with("-I") || a.starts_with("-o") || !a.starts_with('-') => {}
+            a => flags.push(a)
+        }
+    }
+
+    Some((compiler.to_string(), flags.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn check_fingerprint() {
+        let exe = std::env::current_exe().unwrap();
+        let info = crate::binary::fingerprint(exe.to_str().unwrap(), ".").unwrap();
+        assert!(info.size > 0);
+        assert_eq!(info.sha256.len(), 64);
+    }
+
+    #[test]
+    fn check_split_compile_command() {
+        let args: Vec<String> =
+            "/usr/bin/c++ -DNDEBUG -I/src/include -O3 -march=native -o foo.o -c /src/foo.cpp".split(' ').map(String::from).collect();
+        let (compiler, flags) = crate::binary::split_compile_command(&args).unwrap();
+        assert_eq!(compiler, "/usr/bin/c++");
+        assert_eq!(flags, "-DNDEBUG -O3 -march=native");
+    }
+
+    #[test]
+    fn check_compile_command() {
+        let dir = std::env::temp_dir().join(format!("benchviz-compile-{}", std::process::id()));
+        let build = dir.join("build");
+        std::fs::create_dir_all(build.join("bench")).unwrap();
+        std::fs::write(build.join("CMakeCache.txt"), "CMAKE_HOME_DIRECTORY:INTERNAL=/src\n").unwrap();
+        std::fs::write(
+            build.join("compile_commands.json"),
+            r#"[{"directory": "/b", "file": "/src/lib.cpp", "output": "CMakeFiles/lib.dir/lib.cpp.o",
+                 "command": "/usr/bin/c++ -O0 -o CMakeFiles/lib.dir/lib.cpp.o -c /src/lib.cpp"},
+                {"directory": "/b", "file": "/src/bench.cpp",
+                 "command": "/usr/bin/c++ -O3 -o bench/CMakeFiles/bench_sort.dir/bench.cpp.o -c /src/bench.cpp"}]"#
+        )
+        .unwrap();
+
+        let bin = build.join("bench").join("bench_sort");
+        let found = crate::binary::compile_command(bin.to_str().unwrap(), "/nonexistent");
+        assert_eq!(found, Some(("/usr/bin/c++".to_string(), "-O3".to_string())));
+        // Nothing compiles a binary of this name, so nothing is recorded
+        let other = build.join("bench").join("bench_hash");
+        assert_eq!(crate::binary::compile_command(other.to_str().unwrap(), "/nonexistent"), None);
+        // A compile_commands.json above the build tree is never reached
+        std::fs::rename(build.join("compile_commands.json"), dir.join("compile_commands.json")).unwrap();
+        assert_eq!(crate::binary::compile_command(bin.to_str().unwrap(), "/nonexistent"), None);
+        // Even when it is the source root's, it could describe another build tree
+        assert_eq!(crate::binary::compile_command(bin.to_str().unwrap(), dir.to_str().unwrap()), None);
+        // A binary outside any build tree falls back to the source root's
+        let loose = dir.join("bin").join("bench_sort");
+        let found = crate::binary::compile_command(loose.to_str().unwrap(), dir.to_str().unwrap());
+        assert_eq!(found, Some(("/usr/bin/c++".to_string(), "-O3".to_string())));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
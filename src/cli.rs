@@ -3,73 +3,437 @@
 // Module containing io functionality for printing info to users
 // in the CLI environment
 
-use clap;
-
-// Honestly I'm probably just missing how to get clap to do this
-// behavior naturally, but for now we'll just write a nice little function.
-pub fn parse_bench_id<'a>(matches: &'a clap::ArgMatches, id: &str) -> (Option<&'a str>, Option<&'a str>) {
-    match (matches.value_of("name"), matches.value_of(&id)) {
-        (Some(name), Some(run_id)) => (Some(name), Some(run_id)),
-        (None, None) => (None, None),
-        (_, _) => {
-            use colored::*;
-
-            println!(
-                "{}",
-                "Error: must supply either both <name> and <run_id> or neither. \
-                 In the case of neither, a prompt will guide you."
-                    .red()
-            );
-            std::process::exit(1);
+use crate::error::{Error, Result};
+
+// Tags are given as key=value, a bare key is shorthand for key=true.
+pub fn parse_tags(matches: &clap::ArgMatches, id: &str) -> Result<crate::types::RunTags> {
+    let mut tags = crate::types::RunTags::new();
+    for raw in matches.values_of(id).into_iter().flatten() {
+        let (key, value) = match raw.find('=') {
+            Some(pos) => (&raw[..pos], &raw[pos + 1..]),
+            None => (raw, "true")
+        };
+
+        if key.is_empty() {
+            return Err(Error::Invalid(format!("Invalid tag {:?}, expected key=value", raw)));
+        }
+
+        tags.insert(key.to_string(), value.to_string());
+    }
+
+    Ok(tags)
+}
+
+// Benchmark name filters are regexes, where an empty one filters nothing.
+pub fn parse_filter(filter: Option<&str>) -> Result<Option<regex::Regex>> {
+    match filter.filter(|filter| !filter.is_empty()) {
+        Some(filter) => match regex::Regex::new(filter) {
+            Ok(filter) => Ok(Some(filter)),
+            Err(e) => Err(Error::Invalid(format!("Invalid filter {:?}: {}", filter, e)))
+        },
+        None => Ok(None)
+    }
+}
+
+pub fn parse_conditions(raw: Option<&str>) -> Result<Option<crate::name::Conditions>> {
+    match raw.filter(|raw| !raw.is_empty()) {
+        Some(raw) => match crate::name::Conditions::parse(raw) {
+            Some(conditions) => Ok(Some(conditions)),
+            None => Err(Error::Invalid(format!("Invalid conditions {:?}, expected e.g. family=BM_Copy,threads=8", raw)))
+        },
+        None => Ok(None)
+    }
+}
+
+fn parse_percent(raw: &str) -> Result<f64> {
+    match raw.trim_end_matches('%').parse::<f64>() {
+        Ok(percent) if percent >= 0.0 => Ok(percent),
+        _ => Err(Error::Invalid(format!("Invalid percentage {:?}, expected e.g. 2.5", raw)))
+    }
+}
+
+// Metrics given with --metric, real time if none were.
+fn parse_metrics(matches: &clap::ArgMatches) -> Vec<crate::metric::Metric> {
+    let mut metrics: Vec<crate::metric::Metric> =
+        matches.values_of("metric").into_iter().flatten().flat_map(crate::metric::Metric::parse).collect();
+    if metrics.is_empty() {
+        metrics.push(crate::metric::Metric::RealTime);
+    }
+
+    metrics
+}
+
+// The run percentages are relative to, out of those being compared: lhs for the first, rhs for
+// the last, or a position counting from 1.
+fn baseline_column(raw: Option<&str>, runs: usize) -> Result<usize> {
+    match raw {
+        None | Some("lhs") => Ok(0),
+        Some("rhs") => Ok(runs - 1),
+        Some(raw) => match raw.parse::<usize>() {
+            Ok(column) if column >= 1 && column <= runs => Ok(column - 1),
+            _ => Err(Error::Invalid(format!("Invalid baseline {:?}, expected lhs, rhs or a position from 1 to {}", raw, runs)))
         }
     }
 }
 
+// How comparisons should be shown, all of which has defaults.
+fn parse_compare_options(matches: &clap::ArgMatches, runs: usize) -> Result<crate::compare::CompareOptions> {
+    let baseline = match baseline_column(matches.value_of("baseline"), runs)? {
+        0 => crate::compare::Baseline::Lhs,
+        _ => crate::compare::Baseline::Rhs
+    };
+    let sort = match matches.value_of("sort") {
+        Some("name") => crate::compare::SortOrder::Name,
+        Some("delta") => crate::compare::SortOrder::Delta,
+        Some("time") => crate::compare::SortOrder::Time,
+        Some("family") => crate::compare::SortOrder::Family,
+        _ => crate::compare::SortOrder::File
+    };
+    let mut names = crate::compare::NameRules {
+        strip_prefixes: matches.values_of("strip_prefix").into_iter().flatten().map(|s| s.to_string()).collect(),
+        maps:           Vec::new()
+    };
+    for raw in matches.values_of("map").into_iter().flatten() {
+        let mapped = raw.find('=').and_then(|pos| names.add_map(&raw[..pos], &raw[pos + 1..]));
+        if mapped.is_none() {
+            return Err(Error::Invalid(format!("Invalid map {:?}, expected e.g. BM_Std_*=BM_Abseil_*", raw)));
+        }
+    }
+
+    Ok(crate::compare::CompareOptions {
+        baseline,
+        sort,
+        filter: parse_filter(matches.value_of("filter"))?,
+        conditions: parse_conditions(matches.value_of("conditions"))?,
+        only_changed: matches.is_present("only_changed"),
+        metrics: parse_metrics(matches),
+        names,
+        plot: matches.value_of("plot").map(|s| s.to_string())
+    })
+}
+
+// Resolve the benchmark named on the command line, accepting unique prefixes of names.
+fn benchmark_name(matches: &clap::ArgMatches) -> Result<Option<String>> {
+    matches.value_of("name").map(crate::io::resolve_benchmark_name).transpose()
+}
+
+// As benchmark_name, but for commands which change the benchmark, which must be named in full
+fn exact_benchmark_name(matches: &clap::ArgMatches) -> Result<Option<String>> {
+    matches.value_of("name").map(crate::io::exact_benchmark_name).transpose()
+}
+
 // Global queries require no benchmark identifier; they speak on the global state of the program
-pub fn handle_global_query(id: &str, matches: &clap::ArgMatches, f: &Fn() -> ()) {
-    if matches.subcommand_matches(&id).is_some() {
-        f();
+pub fn handle_global_query(id: &str, matches: &clap::ArgMatches, f: &dyn Fn() -> Result<()>) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(_) => f(),
+        None => Ok(())
     }
 }
 
 // Benchmark queries require a valid benchmark identifier; they speak on the specifics for a benchmark.
-pub fn handle_benchmark_query(id: &str, matches: &clap::ArgMatches, f: &Fn(&str) -> ()) {
-    if let Some(v) = matches.subcommand_matches(&id) {
-        f(v.value_of("name").unwrap());
+// They also carry a set of tags, either to attach to new runs or to filter existing ones by.
+pub fn handle_tagged_benchmark_query(
+    id: &str,
+    matches: &clap::ArgMatches,
+    f: &dyn Fn(&str, &crate::types::RunTags) -> Result<()>
+) -> Result<()>
+{
+    match matches.subcommand_matches(id) {
+        Some(v) => f(&exact_benchmark_name(v)?.unwrap(), &parse_tags(v, "tag")?),
+        None => Ok(())
     }
 }
 
-pub fn handle_run_data_query(id: &str, matches: &clap::ArgMatches, f: &Fn(&str, &crate::types::RunId) -> (), g: &Fn() -> ()) {
-    if let Some(v) = matches.subcommand_matches(&id) {
-        match parse_bench_id(&v, "run_id") {
-            (Some(name), Some(run_id)) => {
-                if let Some(parsed_run_id) = crate::io::parse_run_id(&name, &run_id) {
-                    f(&name, &parsed_run_id);
-                }
-            }
+type InfoFn = dyn Fn(&str, &crate::types::RunTags, &crate::types::RunId) -> Result<()>;
+
+// Info queries are benchmark queries which can be narrowed to a selection of runs.
+pub fn handle_info_query(id: &str, matches: &clap::ArgMatches, f: &InfoFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let name = &benchmark_name(v)?.unwrap();
+            let parsed_run_id = crate::io::parse_run_id(name, v.value_of("runs").unwrap_or("*"))?;
+            f(name, &parse_tags(v, "tag")?, &parsed_run_id)
+        }
+        None => Ok(())
+    }
+}
+
+type ImportFn = dyn Fn(&str, &str, &crate::types::RunTags, Option<&str>) -> Result<()>;
+
+// Import queries record a run from an existing google/benchmark json file.
+pub fn handle_import_query(id: &str, matches: &clap::ArgMatches, f: &ImportFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => f(&exact_benchmark_name(v)?.unwrap(), v.value_of("path").unwrap(), &parse_tags(v, "tag")?, v.value_of("message")),
+        None => Ok(())
+    }
+}
+
+type TagFn = dyn Fn(&str, &str, &crate::types::RunTags, &[&str]) -> Result<()>;
+
+// Tag queries add and remove tags on a single run of a benchmark.
+pub fn handle_tag_query(id: &str, matches: &clap::ArgMatches, f: &TagFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let name = &exact_benchmark_name(v)?.unwrap();
+            let removals: Vec<&str> = v.values_of("remove").into_iter().flatten().collect();
+            f(name, v.value_of("run_id").unwrap(), &parse_tags(v, "tags")?, &removals)
+        }
+        None => Ok(())
+    }
+}
+
+// Path queries operate on a location on disk, rather than an existing benchmark.
+pub fn handle_path_query(id: &str, matches: &clap::ArgMatches, f: &dyn Fn(&str) -> Result<()>) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => f(v.value_of("path").unwrap()),
+        None => Ok(())
+    }
+}
+
+// Run data queries take an optional benchmark and run; supplying neither brings up a prompt,
+// and supplying only the benchmark applies to the benchmark as a whole.
+pub fn handle_run_data_query(
+    id: &str,
+    matches: &clap::ArgMatches,
+    f: &dyn Fn(&str, &str) -> Result<()>,
+    g: &dyn Fn() -> Result<()>,
+    h: &dyn Fn(&str, bool) -> Result<()>
+) -> Result<()>
+{
+    match matches.subcommand_matches(id) {
+        Some(v) => match (exact_benchmark_name(v)?.as_deref(), v.value_of("run_id")) {
+            (Some(name), Some(run_id)) => f(name, run_id),
+            (Some(name), None) => h(name, v.is_present("yes")),
             (None, None) => g(),
-            (_, _) => unreachable!()
+            (None, Some(_)) => unreachable!()
+        },
+        None => Ok(())
+    }
+}
+
+// Rename queries map an existing benchmark identifier onto a new one.
+pub fn handle_rename_query(id: &str, matches: &clap::ArgMatches, f: &dyn Fn(&str, &str) -> Result<()>) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => f(&exact_benchmark_name(v)?.unwrap(), v.value_of("new_name").unwrap()),
+        None => Ok(())
+    }
+}
+
+// Edit queries take a benchmark identifier, plus whichever header fields were given as flags.
+pub fn handle_edit_query(
+    id: &str,
+    matches: &clap::ArgMatches,
+    f: &dyn Fn(&str, crate::types::HeaderEdits) -> Result<()>
+) -> Result<()>
+{
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let edits = crate::types::HeaderEdits {
+                source_root:   v.value_of("source_root").map(|s| s.to_string()),
+                source_bin:    v.value_of("source_bin").map(|s| s.to_string()),
+                description:   v.value_of("description").map(|s| s.to_string()),
+                noise_percent: v.value_of("noise").map(parse_percent).transpose()?
+            };
+            f(&exact_benchmark_name(v)?.unwrap(), edits)
+        }
+        None => Ok(())
+    }
+}
+
+type AnalyzeFn = dyn Fn(&str, &[crate::metric::Metric], Option<&regex::Regex>) -> Result<()>;
+
+// Analysis queries look over the whole history of a benchmark, for the given metrics and
+// optionally only some of its functions.
+pub fn handle_analyze_query(id: &str, matches: &clap::ArgMatches, f: &AnalyzeFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => f(&benchmark_name(v)?.unwrap(), &parse_metrics(v), parse_filter(v.value_of("filter"))?.as_ref()),
+        None => Ok(())
+    }
+}
+
+// Which results to look at, by regex over names and by the parts of them
+pub struct Selection {
+    pub filter:     Option<regex::Regex>,
+    pub conditions: Option<crate::name::Conditions>
+}
+
+impl Selection {
+    fn parse(matches: &clap::ArgMatches) -> Result<Selection> {
+        Ok(Selection {
+            filter:     parse_filter(matches.value_of("filter"))?,
+            conditions: parse_conditions(matches.value_of("conditions"))?
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.is_match(name))
+            && self.conditions.as_ref().is_none_or(|conditions| conditions.is_match(name))
+    }
+}
+
+// One run given, the latest not excluded as an outlier by default, or two to see what changed
+// between them
+fn latest_or_given_runs(name: &str, matches: &clap::ArgMatches) -> Result<Vec<crate::types::RunId>> {
+    let run_ids: Vec<&str> = matches.values_of("run_id_1").into_iter().chain(matches.values_of("run_id_2")).flatten().collect();
+    if run_ids.is_empty() {
+        return Ok(vec![crate::types::RunId::Index(crate::io::latest_included_runs(name, 1)?[0])]);
+    }
+    run_ids.iter().map(|run_id| crate::io::parse_run_id(name, run_id)).collect()
+}
+
+type RunsViewFn = dyn Fn(&str, Vec<crate::types::RunId>, &crate::metric::Metric, &Selection, Option<&str>) -> Result<()>;
+
+// Complexity queries take a benchmark with one run, the latest by default, or two to see what
+// changed between them. Only times can be fitted.
+pub fn handle_complexity_query(id: &str, matches: &clap::ArgMatches, f: &RunsViewFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let name = &benchmark_name(v)?.unwrap();
+            let runs = latest_or_given_runs(name, v)?;
+            let metric = match parse_metrics(v).as_slice() {
+                [metric] if metric.is_time() => metric.clone(),
+                _ => return Err(Error::Invalid("Complexity can only be fitted to one of real or cpu time.".to_string()))
+            };
+            f(name, runs, &metric, &Selection::parse(v)?, v.value_of("plot"))
         }
+        None => Ok(())
     }
 }
 
+// Scaling queries take runs as complexity queries do, and any one metric.
+pub fn handle_scaling_query(id: &str, matches: &clap::ArgMatches, f: &RunsViewFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let name = &benchmark_name(v)?.unwrap();
+            let runs = latest_or_given_runs(name, v)?;
+            let metric = match parse_metrics(v).as_slice() {
+                [metric] => metric.clone(),
+                _ => return Err(Error::Invalid("Scaling is shown for one metric at a time.".to_string()))
+            };
+            f(name, runs, &metric, &Selection::parse(v)?, v.value_of("plot"))
+        }
+        None => Ok(())
+    }
+}
+
+type PivotFn = dyn Fn(&str, crate::types::RunId, &str, &crate::metric::Metric, &Selection, Option<&str>) -> Result<()>;
+
+// Pivot queries take a run of a benchmark, the latest by default, and the part of its names to
+// put across.
+pub fn handle_pivot_query(id: &str, matches: &clap::ArgMatches, f: &PivotFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let name = &benchmark_name(v)?.unwrap();
+            let parsed_run_id = crate::io::parse_run_id(name, v.value_of("run_id").unwrap_or("latest"))?;
+            let metric = match parse_metrics(v).as_slice() {
+                [metric] => metric.clone(),
+                _ => return Err(Error::Invalid("Pivot tables show one metric at a time.".to_string()))
+            };
+            f(name, parsed_run_id, v.value_of("on").unwrap(), &metric, &Selection::parse(v)?, v.value_of("plot"))
+        }
+        None => Ok(())
+    }
+}
+
+type OutliersFn = dyn Fn(&str, Option<f64>, bool) -> Result<()>;
+
+// Outlier queries take a benchmark, an optional threshold and whether to exclude what's found.
+pub fn handle_outliers_query(id: &str, matches: &clap::ArgMatches, f: &OutliersFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let threshold = v.value_of("threshold").map(parse_percent).transpose()?;
+            // Excluding runs changes the benchmark, so it needs its full name
+            let exclude = v.is_present("exclude");
+            let name = if exclude { exact_benchmark_name(v)? } else { benchmark_name(v)? };
+            f(&name.unwrap(), threshold, exclude)
+        }
+        None => Ok(())
+    }
+}
+
+type AnnotateFn = dyn Fn(&str, &crate::types::RunId, Option<&str>, bool) -> Result<()>;
+
+// Annotation queries take a single run of a benchmark, and optionally the text to attach to it.
+pub fn handle_annotate_query(id: &str, matches: &clap::ArgMatches, f: &AnnotateFn) -> Result<()> {
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let name = &exact_benchmark_name(v)?.unwrap();
+            let parsed_run_id = crate::io::parse_run_id(name, v.value_of("run_id").unwrap())?;
+            f(name, &parsed_run_id, v.value_of("text"), v.is_present("append"))
+        }
+        None => Ok(())
+    }
+}
+
+// A run of a benchmark given as <name>:<run>, where the run defaults to the latest not
+// excluded as an outlier.
+fn benchmark_run(raw: &str) -> Result<(String, crate::types::RunId)> {
+    let (name, run_id) = match raw.find(':') {
+        Some(pos) => (&raw[..pos], &raw[pos + 1..]),
+        None => (raw, "")
+    };
+    let name = crate::io::resolve_benchmark_name(name)?;
+    let run_id = match run_id {
+        "" => crate::types::RunId::Index(crate::io::latest_included_runs(&name, 1)?[0]),
+        run_id => crate::io::parse_run_id(&name, run_id)?
+    };
+    Ok((name, run_id))
+}
+
+type CompareFn = dyn Fn(&str, crate::types::RunId, crate::types::RunId, &crate::compare::CompareOptions) -> Result<()>;
+type CrossCompareFn =
+    dyn Fn(&str, crate::types::RunId, &str, crate::types::RunId, &crate::compare::CompareOptions) -> Result<()>;
+type MatrixFn = dyn Fn(&str, Vec<crate::types::RunId>, usize, &crate::compare::CompareOptions) -> Result<()>;
+
+// Multi run data queries take an optional benchmark and a pair of its runs, plus options on
+// how to compare them; supplying no benchmark brings up a prompt. The runs may instead be of
+// two different benchmarks, given as <name>:<run> <name>:<run>, or more than two runs of one
+// benchmark, compared against a baseline column.
 pub fn handle_multi_run_data_query(
     id: &str,
     matches: &clap::ArgMatches,
-    f: &Fn(&str, crate::types::RunId, crate::types::RunId) -> (),
-    g: &Fn() -> ()
-)
+    f: &CompareFn,
+    g: &dyn Fn(&crate::compare::CompareOptions) -> Result<()>,
+    h: &CrossCompareFn,
+    i: &MatrixFn
+) -> Result<()>
 {
-    if let Some(v) = matches.subcommand_matches(&id) {
-        match (v.value_of("name"), v.value_of("run_id_1"), v.value_of("run_id_2")) {
-            (Some(name), Some(run_id_1), Some(run_id_2)) => {
-                match (crate::io::parse_run_id(&name, &run_id_1), crate::io::parse_run_id(&name, &run_id_2)) {
-                    (Some(parsed_run_id_1), Some(parsed_run_id_2)) => f(&name, parsed_run_id_1, parsed_run_id_2),
-                    (_, _) => {}
+    match matches.subcommand_matches(id) {
+        Some(v) => {
+            let run_ids: Vec<&str> = v.values_of("run_id_1").into_iter().chain(v.values_of("run_id_2")).flatten().collect();
+            let options = parse_compare_options(v, std::cmp::max(run_ids.len(), 2))?;
+            if run_ids.len() > 2 && !v.value_of("name").unwrap_or_default().contains(':') {
+                let name = &benchmark_name(v)?.unwrap();
+                let runs = run_ids.iter().map(|run_id| crate::io::parse_run_id(name, run_id)).collect::<Result<Vec<_>>>()?;
+                return i(name, runs, baseline_column(v.value_of("baseline"), run_ids.len())?, &options);
+            }
+
+            if let Some(lhs) = v.value_of("name").filter(|name| name.contains(':')) {
+                return match (v.value_of("run_id_1"), v.value_of("run_id_2")) {
+                    (Some(rhs), None) => {
+                        let ((lhs_name, lhs_run), (rhs_name, rhs_run)) = (benchmark_run(lhs)?, benchmark_run(rhs)?);
+                        h(&lhs_name, lhs_run, &rhs_name, rhs_run, &options)
+                    }
+                    (_, _) => Err(Error::Invalid(format!(
+                        "Comparing {:?} with another benchmark needs exactly one more run, e.g. other_bench:latest",
+                        lhs
+                    )))
+                };
+            }
+
+            match (benchmark_name(v)?.as_deref(), v.value_of("run_id_1"), v.value_of("run_id_2")) {
+                (Some(name), Some(run_id_1), Some(run_id_2)) => {
+                    f(name, crate::io::parse_run_id(name, run_id_1)?, crate::io::parse_run_id(name, run_id_2)?, &options)
+                }
+                // With just a benchmark, compare the two most recent runs not excluded as outliers
+                (Some(name), None, None) => {
+                    let runs = crate::io::latest_included_runs(name, 2)?;
+                    f(name, crate::types::RunId::Index(runs[0]), crate::types::RunId::Index(runs[1]), &options)
                 }
+                (None, None, None) => g(&options),
+                (_, _, _) => Err(Error::Invalid("Give both runs to compare, or neither for the two most recent.".to_string()))
             }
-            (None, None, None) => g(),
-            (_, _, _) => unreachable!()
         }
+        None => Ok(())
     }
 }
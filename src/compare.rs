@@ -0,0 +1,409 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing comparisons between two recorded runs of a benchmark, as
+// data which the CLI (or anything else) can render however it likes.
+
+use serde::Serialize;
+
+use crate::metric::Metric;
+use crate::types::{BenchResult, BinaryInfo, IndividualBenchInfo};
+
+// Differences smaller than this percentage are treated as noise
+pub const NOISE_PERCENT: f64 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Improvement,
+    Regression,
+    Unchanged
+}
+
+// The run percentages are relative to. Conventionally that's the earlier,
+// left hand run.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Baseline {
+    Lhs,
+    Rhs
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    // As google/benchmark ran them
+    File,
+    Name,
+    // Biggest regressions first
+    Delta,
+    // Slowest first, by baseline time
+    Time,
+    // Grouped by family, then by arguments in increasing order
+    Family
+}
+
+// How to line up the results of two different benchmarks, which name their
+// cases differently: prefixes stripped from both sides, then globs such as
+// BM_Std_* => BM_Abseil_* rewriting the first side's names into the second's.
+#[derive(Clone, Debug, Default)]
+pub struct NameRules {
+    pub strip_prefixes: Vec<String>,
+    pub maps:           Vec<(regex::Regex, String)>
+}
+
+impl NameRules {
+    // Add a glob rewrite, where each * in to is whatever the matching * in from matched.
+    // None if to has more *s than from.
+    pub fn add_map(&mut self, from: &str, to: &str) -> Option<()> {
+        let captures = from.matches('*').count();
+        if to.matches('*').count() > captures {
+            return None;
+        }
+
+        let pattern: Vec<String> = from.split('*').map(regex::escape).collect();
+        let regex = regex::Regex::new(&format!("^{}$", pattern.join("(.*)"))).ok()?;
+        let mut replacement = String::new();
+        for (i, literal) in to.split('*').enumerate() {
+            if i > 0 {
+                replacement.push_str(&format!("${{{}}}", i));
+            }
+            replacement.push_str(&literal.replace('$', "$$"));
+        }
+
+        self.maps.push((regex, replacement));
+        Some(())
+    }
+
+    fn strip(&self, name: &str) -> String {
+        let prefix = self.strip_prefixes.iter().find(|prefix| name.starts_with(prefix.as_str()));
+        name[prefix.map_or(0, |prefix| prefix.len())..].to_string()
+    }
+
+    // The name a result of the first benchmark is matched on, the first matching map wins
+    pub fn lhs_key(&self, name: &str) -> String {
+        let name = self.strip(name);
+        match self.maps.iter().find(|(from, _)| from.is_match(&name)) {
+            Some((from, to)) => from.replace(&name, to.as_str()).into_owned(),
+            None => name
+        }
+    }
+
+    pub fn rhs_key(&self, name: &str) -> String {
+        self.strip(name)
+    }
+}
+
+// How to present a comparison, beyond what's being compared
+#[derive(Clone)]
+pub struct CompareOptions {
+    pub baseline:     Baseline,
+    pub sort:         SortOrder,
+    pub filter:       Option<regex::Regex>,
+    // On the parts of names, such as family=BM_Copy,threads=8
+    pub conditions:   Option<crate::name::Conditions>,
+    pub only_changed: bool,
+    // Each benchmark gets a row per metric, in this order
+    pub metrics:      Vec<Metric>,
+    // Only used when comparing two different benchmarks
+    pub names:        NameRules,
+    // Where to write a heatmap of the comparison, as SVG
+    pub plot:         Option<String>
+}
+
+impl Default for CompareOptions {
+    fn default() -> CompareOptions {
+        CompareOptions {
+            baseline:     Baseline::Lhs,
+            sort:         SortOrder::File,
+            filter:       None,
+            conditions:   None,
+            only_changed: false,
+            metrics:      vec![Metric::RealTime],
+            names:        NameRules::default(),
+            plot:         None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ComparisonRow {
+    pub name:         String,
+    pub metric:       Metric,
+    pub unit:         String,
+    pub lhs_value:    f64,
+    pub rhs_value:    f64,
+    pub abs_diff:     f64,
+    // Relative to the baseline value
+    pub percent_diff: f64,
+    // Above 1 when rhs is better, whichever direction that is for the metric
+    pub speedup:      f64
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Comparison {
+    pub lhs:        usize,
+    pub rhs:        usize,
+    pub lhs_hash:   String,
+    pub rhs_hash:   String,
+    pub lhs_binary: Option<BinaryInfo>,
+    pub rhs_binary: Option<BinaryInfo>,
+    pub baseline:   Baseline,
+    pub rows:       Vec<ComparisonRow>,
+    // Results which had no counterpart in the other run, by name
+    pub unmatched:  Vec<String>
+}
+
+impl ComparisonRow {
+    pub fn change(&self, noise_percent: f64) -> Change {
+        if self.percent_diff.abs() < noise_percent || self.percent_diff.is_nan() {
+            Change::Unchanged
+        } else if self.speedup < 1.0 {
+            Change::Regression
+        } else {
+            Change::Improvement
+        }
+    }
+
+    // The percentage change, positive when it's for the worse
+    pub fn regression_percent(&self) -> f64 {
+        if self.metric.higher_is_better() {
+            -self.percent_diff
+        } else {
+            self.percent_diff
+        }
+    }
+}
+
+pub(crate) fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    crate::name::BenchName::parse(a).natural_cmp(&crate::name::BenchName::parse(b))
+}
+
+impl Comparison {
+    // Filter and sort the rows as asked, changes within the noise band count as unchanged
+    pub fn arranged(mut self, options: &CompareOptions, noise_percent: f64) -> Comparison {
+        if let Some(filter) = &options.filter {
+            self.rows.retain(|row| filter.is_match(&row.name));
+        }
+
+        if let Some(conditions) = &options.conditions {
+            self.rows.retain(|row| conditions.is_match(&row.name));
+        }
+
+        if options.only_changed {
+            self.rows.retain(|row| row.change(noise_percent) != Change::Unchanged);
+        }
+
+        let baseline = self.baseline;
+        let descending = |a: f64, b: f64| b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal);
+        match options.sort {
+            SortOrder::File => {}
+            SortOrder::Name => self.rows.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortOrder::Delta => self.rows.sort_by(|a, b| descending(a.regression_percent(), b.regression_percent())),
+            SortOrder::Time => self.rows.sort_by(|a, b| descending(a.baseline_value(baseline), b.baseline_value(baseline))),
+            SortOrder::Family => self.rows.sort_by(|a, b| natural_cmp(&a.name, &b.name))
+        }
+
+        self
+    }
+}
+
+impl ComparisonRow {
+    pub fn baseline_value(&self, baseline: Baseline) -> f64 {
+        match baseline {
+            Baseline::Lhs => self.lhs_value,
+            Baseline::Rhs => self.rhs_value
+        }
+    }
+}
+
+// One row per metric for a pair of results, skipping metrics either is missing
+fn compare_results(
+    name: &str,
+    lhs: &BenchResult,
+    rhs: &BenchResult,
+    baseline: Baseline,
+    metrics: &[Metric],
+    rows: &mut Vec<ComparisonRow>
+)
+{
+    for metric in metrics {
+        let (lhs_value, rhs_value) = match (metric.value(lhs), metric.value(rhs)) {
+            (Some(lhs_value), Some(rhs_value)) => (lhs_value, rhs_value),
+            _ => continue
+        };
+        let baseline_value = if baseline == Baseline::Lhs { lhs_value } else { rhs_value };
+        rows.push(ComparisonRow {
+            name: name.to_string(),
+            metric: metric.clone(),
+            unit: metric.unit(lhs),
+            lhs_value,
+            rhs_value,
+            abs_diff: rhs_value - lhs_value,
+            percent_diff: 100.0 * (rhs_value - lhs_value) / baseline_value,
+            speedup: if metric.higher_is_better() { rhs_value / lhs_value } else { lhs_value / rhs_value }
+        });
+    }
+}
+
+fn comparison(
+    lhs_info: &IndividualBenchInfo,
+    lhs: usize,
+    rhs_info: &IndividualBenchInfo,
+    rhs: usize,
+    baseline: Baseline,
+    rows: Vec<ComparisonRow>,
+    unmatched: Vec<String>
+) -> Comparison
+{
+    Comparison {
+        lhs,
+        rhs,
+        lhs_hash: lhs_info.source_hashes.get(lhs).cloned().unwrap_or_default(),
+        rhs_hash: rhs_info.source_hashes.get(rhs).cloned().unwrap_or_default(),
+        lhs_binary: lhs_info.binaries.get(lhs).cloned().flatten(),
+        rhs_binary: rhs_info.binaries.get(rhs).cloned().flatten(),
+        baseline,
+        rows,
+        unmatched
+    }
+}
+
+// Compare two runs by index, or None if either doesn't exist. Results are matched by
+// name, so benchmarks added or removed between the runs are left unmatched rather than
+// shifting the rest. Benchmarks missing one of the metrics, in either run, have no row for it.
+pub fn compare_runs(
+    info: &IndividualBenchInfo,
+    lhs: usize,
+    rhs: usize,
+    baseline: Baseline,
+    metrics: &[Metric]
+) -> Option<Comparison>
+{
+    compare_benchmarks(info, lhs, info, rhs, baseline, metrics, &NameRules::default())
+}
+
+// Compare runs of two different benchmarks, matching their results by name as
+// rewritten by the rules. Rows are named as the second benchmark's results are,
+// less any stripped prefix, and in the first benchmark's order. Repetitions of a
+// name pair up in the order they were run.
+pub fn compare_benchmarks(
+    lhs_info: &IndividualBenchInfo,
+    lhs: usize,
+    rhs_info: &IndividualBenchInfo,
+    rhs: usize,
+    baseline: Baseline,
+    metrics: &[Metric],
+    rules: &NameRules
+) -> Option<Comparison>
+{
+    let lhs_results = lhs_info.benchmarks.get(lhs)?;
+    let rhs_results = rhs_info.benchmarks.get(rhs)?;
+
+    let mut rows = Vec::new();
+    let mut unmatched = Vec::new();
+    let mut matched = vec![false; rhs_results.len()];
+    for lhs_result in lhs_results {
+        let key = rules.lhs_key(&lhs_result.name);
+        match (0..rhs_results.len()).find(|i| !matched[*i] && rules.rhs_key(&rhs_results[*i].name) == key) {
+            Some(i) => {
+                matched[i] = true;
+                compare_results(&key, lhs_result, &rhs_results[i], baseline, metrics, &mut rows);
+            }
+            None => unmatched.push(lhs_result.name.clone())
+        }
+    }
+
+    unmatched.extend(rhs_results.iter().zip(matched).filter(|(_, matched)| !matched).map(|(result, _)| result.name.clone()));
+    Some(comparison(lhs_info, lhs, rhs_info, rhs, baseline, rows, unmatched))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::{compare_benchmarks, compare_runs, Baseline, Change, CompareOptions, NameRules, SortOrder};
+    use crate::metric::Metric;
+    use crate::types::{BenchResult, IndividualBenchInfo};
+
+    #[test]
+    fn check_change() {
+        let run = |times: &[f64]| -> Vec<BenchResult> {
+            times
+                .iter()
+                .enumerate()
+                .map(|(i, time)| BenchResult {
+                    items_per_second: Some(1e9 / time),
+                    ..BenchResult::timed(&format!("BM_{}", i), *time)
+                })
+                .collect()
+        };
+
+        let info = IndividualBenchInfo {
+            benchmarks: vec![run(&[100.0, 100.0, 100.0]), run(&[120.0, 80.0, 100.5])],
+            commentary: vec!["".to_string(); 2],
+            source_hashes: vec!["".to_string(); 2],
+            ..Default::default()
+        };
+
+        let comparison = compare_runs(&info, 0, 1, Baseline::Lhs, &[Metric::RealTime]).unwrap();
+        let changes: Vec<Change> = comparison.rows.iter().map(|row| row.change(1.0)).collect();
+        assert_eq!(changes, vec![Change::Regression, Change::Improvement, Change::Unchanged]);
+        assert_eq!(comparison.rows[0].percent_diff, 20.0);
+        assert!(compare_runs(&info, 0, 2, Baseline::Lhs, &[Metric::RealTime]).is_none());
+
+        let options = CompareOptions { sort: SortOrder::Delta, only_changed: true, ..Default::default() };
+        let names: Vec<String> = comparison.arranged(&options, 1.0).rows.into_iter().map(|row| row.name).collect();
+        assert_eq!(names, vec!["BM_0", "BM_1"]);
+
+        let options = CompareOptions { filter: Some(regex::Regex::new("_[12]$").unwrap()), ..Default::default() };
+        let comparison = compare_runs(&info, 0, 1, Baseline::Rhs, &[Metric::RealTime]).unwrap().arranged(&options, 1.0);
+        assert_eq!(comparison.rows.len(), 2);
+        assert_eq!(comparison.rows[0].percent_diff, -25.0);
+
+        // Throughput drops when time goes up, which is still a regression
+        let comparison = compare_runs(&info, 0, 1, Baseline::Lhs, &[Metric::RealTime, Metric::ItemsPerSecond]).unwrap();
+        assert_eq!(comparison.rows.len(), 6);
+        assert_eq!(comparison.rows[1].metric, Metric::ItemsPerSecond);
+        assert_eq!(comparison.rows[1].change(1.0), Change::Regression);
+        assert_eq!(comparison.rows[3].change(1.0), Change::Improvement);
+        assert!(compare_runs(&info, 0, 1, Baseline::Lhs, &[Metric::BytesPerSecond]).unwrap().rows.is_empty());
+    }
+
+    #[test]
+    fn check_benchmark_matching() {
+        let info = |names: &[&str]| IndividualBenchInfo {
+            benchmarks: vec![names
+                .iter()
+                .map(|name| BenchResult::timed(name, 1.0))
+                .collect()],
+            commentary: vec!["".to_string()],
+            source_hashes: vec!["".to_string()],
+            ..Default::default()
+        };
+
+        let mut rules = NameRules::default();
+        assert!(rules.add_map("BM_Std_*/*", "BM_Abseil_*/*").is_some());
+        assert!(rules.add_map("BM_Std", "BM_*").is_none());
+        assert_eq!(rules.lhs_key("BM_Std_Insert/64"), "BM_Abseil_Insert/64");
+        assert_eq!(rules.lhs_key("BM_Std_Insert"), "BM_Std_Insert");
+
+        let (lhs, rhs) = (info(&["BM_Std_Insert/8", "BM_Std_Find/8"]), info(&["BM_Abseil_Find/8", "BM_Abseil_Erase/8"]));
+        let comparison = compare_benchmarks(&lhs, 0, &rhs, 0, Baseline::Lhs, &[Metric::RealTime], &rules).unwrap();
+        let names: Vec<&str> = comparison.rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, vec!["BM_Abseil_Find/8"]);
+        assert_eq!(comparison.unmatched, vec!["BM_Std_Insert/8", "BM_Abseil_Erase/8"]);
+
+        let rules = NameRules { strip_prefixes: vec!["BM_Std_".to_string(), "BM_Abseil_".to_string()], ..Default::default() };
+        let comparison = compare_benchmarks(&lhs, 0, &rhs, 0, Baseline::Lhs, &[Metric::RealTime], &rules).unwrap();
+        assert_eq!(comparison.rows[0].name, "Find/8");
+        assert!(compare_benchmarks(&lhs, 0, &rhs, 1, Baseline::Lhs, &[Metric::RealTime], &rules).is_none());
+
+        // Runs of one benchmark are matched by name too, so one added and one removed
+        // don't shift the rest, and repetitions pair up in order
+        let mut runs = info(&["BM_A", "BM_B", "BM_C", "BM_C"]);
+        runs.benchmarks.extend(info(&["BM_A", "BM_New", "BM_C", "BM_C"]).benchmarks);
+        runs.commentary.push("".to_string());
+        runs.source_hashes.push("".to_string());
+        let comparison = compare_runs(&runs, 0, 1, Baseline::Lhs, &[Metric::RealTime]).unwrap();
+        let names: Vec<&str> = comparison.rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, vec!["BM_A", "BM_C", "BM_C"]);
+        assert_eq!(comparison.unmatched, vec!["BM_B", "BM_New"]);
+    }
+}
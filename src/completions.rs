@@ -0,0 +1,252 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing shell completion scripts. The static parts come from clap,
+// and we wrap them so benchmark names and run ids complete from whatever is
+// currently recorded, by calling back into `benchviz candidates` with any
+// --data-dir from the command line being completed, so they come from the same
+// workspace the command will use.
+
+use std::io::Write;
+
+// Subcommands whose first positional is a benchmark name
+const NAME_SUBCOMMANDS: &[&str] = &[
+    "info",
+    "run",
+    "import",
+    "tag",
+    "remove",
+    "rename",
+    "edit",
+    "annotate",
+    "compare",
+    "analyze",
+    "outliers",
+    "complexity",
+    "pivot",
+    "scaling"
+];
+
+// (subcommand, positional index) pairs which take a run id
+const RUN_POSITIONS: &[(&str, usize)] = &[
+    ("info", 1),
+    ("tag", 1),
+    ("remove", 1),
+    ("annotate", 1),
+    ("compare", 1),
+    ("compare", 2),
+    ("compare", 3),
+    ("compare", 4),
+    ("compare", 5),
+    ("compare", 6),
+    ("complexity", 1),
+    ("complexity", 2),
+    ("pivot", 1),
+    ("scaling", 1),
+    ("scaling", 2)
+];
+
+// Flags which consume the following word, so it isn't mistaken for a positional
+const VALUE_FLAGS: &[&str] = &[
+    "--data-dir",
+    "--format",
+    "-t",
+    "--tag",
+    "-m",
+    "--message",
+    "-r",
+    "--remove",
+    "--source-root",
+    "--source-bin",
+    "--description",
+    "--noise",
+    "--baseline",
+    "--sort",
+    "--filter",
+    "--metric",
+    "--strip-prefix",
+    "--map",
+    "--plot",
+    "--threshold",
+    "--where",
+    "--on"
+];
+
+fn name_states() -> Vec<String> {
+    NAME_SUBCOMMANDS.iter().map(|sub| format!("{}:0", sub)).collect()
+}
+
+fn run_states() -> Vec<String> {
+    RUN_POSITIONS.iter().map(|(sub, pos)| format!("{}:{}", sub, pos)).collect()
+}
+
+pub fn generate(mut app: clap::App, shell: &str) -> crate::error::Result<()> {
+    let parsed_shell = match shell {
+        "bash" => clap::Shell::Bash,
+        "zsh" => clap::Shell::Zsh,
+        "fish" => clap::Shell::Fish,
+        _ => unreachable!()
+    };
+
+    let mut generated = vec![];
+    app.gen_completions_to("benchviz", parsed_shell, &mut generated);
+    let generated = String::from_utf8_lossy(&generated).to_string();
+
+    let script = match parsed_shell {
+        clap::Shell::Bash => format!("{}\n{}", generated, bash_wrapper()),
+        // clap's zsh script ends by invoking its completion function, so take
+        // that over and call our wrapper instead
+        clap::Shell::Zsh => {
+            let trimmed = generated.trim_end().trim_end_matches("_benchviz \"$@\"");
+            format!("{}\n{}", trimmed, zsh_wrapper())
+        }
+        _ => format!("{}\n{}", generated, fish_wrapper())
+    };
+
+    std::io::stdout().write_all(script.as_bytes()).map_err(crate::error::Error::io("Failed to write the completion script"))
+}
+
+fn bash_wrapper() -> String {
+    format!(
+        r#"_benchviz_dynamic() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" sub="" name="" pos=0 skip=0 flag="" data_dir="" i word
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${{COMP_WORDS[i]}}"
+        if ((skip)); then
+            # Bash splits --flag=value into three words
+            [[ "$word" == "=" ]] && continue
+            skip=0
+            [[ "$flag" == --data-dir ]] && data_dir="$word"
+            continue
+        fi
+        case "$word" in
+            --data-dir=*) data_dir="${{word#--data-dir=}}"; continue ;;
+            {value_flags}) skip=1; flag="$word"; continue ;;
+            -*) continue ;;
+        esac
+        if [[ -z "$sub" ]]; then sub="$word"; else [[ $pos -eq 0 ]] && name="$word"; ((pos++)); fi
+    done
+
+    local dir=()
+    [[ -n "$data_dir" ]] && dir=(--data-dir "${{data_dir/#\~/$HOME}}")
+    if [[ "$cur" != -* && $skip -eq 0 ]]; then
+        case "$sub:$pos" in
+            {names})
+                COMPREPLY=($(compgen -W "$(benchviz "${{dir[@]}}" candidates names 2>/dev/null)" -- "$cur"))
+                return 0 ;;
+            {runs})
+                COMPREPLY=($(compgen -W "$(benchviz "${{dir[@]}}" candidates runs "$name" 2>/dev/null)" -- "$cur"))
+                return 0 ;;
+        esac
+    fi
+
+    _benchviz "$@"
+}}
+
+complete -F _benchviz_dynamic -o bashdefault -o default benchviz
+"#,
+        value_flags = VALUE_FLAGS.join("|"),
+        names = name_states().join("|"),
+        runs = run_states().join("|")
+    )
+}
+
+fn zsh_wrapper() -> String {
+    format!(
+        r#"_benchviz_dynamic() {{
+    local sub="" name="" pos=0 skip=0 flag="" data_dir="" i word
+    for ((i = 2; i < CURRENT; i++)); do
+        word="${{words[i]}}"
+        if ((skip)); then
+            skip=0
+            [[ "$flag" == --data-dir ]] && data_dir="$word"
+            continue
+        fi
+        case "$word" in
+            (--data-dir=*) data_dir="${{word#--data-dir=}}"; continue ;;
+            ({value_flags}) skip=1; flag="$word"; continue ;;
+            (-*) continue ;;
+        esac
+        if [[ -z "$sub" ]]; then sub="$word"; else [[ $pos -eq 0 ]] && name="$word"; ((pos++)); fi
+    done
+
+    local -a dir
+    [[ -n "$data_dir" ]] && dir=(--data-dir "${{data_dir/#\~/$HOME}}")
+    if [[ "${{words[CURRENT]}}" != -* && $skip -eq 0 ]]; then
+        case "$sub:$pos" in
+            ({names})
+                compadd -- ${{(f)"$(benchviz "${{dir[@]}}" candidates names 2>/dev/null)"}}
+                return ;;
+            ({runs})
+                compadd -- ${{(f)"$(benchviz "${{dir[@]}}" candidates runs "$name" 2>/dev/null)"}}
+                return ;;
+        esac
+    fi
+
+    _benchviz "$@"
+}}
+
+_benchviz_dynamic "$@"
+"#,
+        value_flags = VALUE_FLAGS.join("|"),
+        names = name_states().join("|"),
+        runs = run_states().join("|")
+    )
+}
+
+fn fish_wrapper() -> String {
+    format!(
+        r#"function __benchviz_state
+    set -l sub ""; set -l name ""; set -l pos 0; set -l skip 0; set -l flag ""; set -l data_dir ""
+    for word in (commandline -opc)[2..-1]
+        if test $skip -eq 1
+            set skip 0
+            test "$flag" = --data-dir; and set data_dir $word
+            continue
+        end
+        switch $word
+            case '--data-dir=*'
+                set data_dir (string replace -- --data-dir= '' $word); continue
+            case {value_flags}
+                set skip 1; set flag $word; continue
+            case '-*'
+                continue
+        end
+        if test -z "$sub"
+            set sub $word
+        else
+            test $pos -eq 0; and set name $word
+            set pos (math $pos + 1)
+        end
+    end
+    echo "$sub:$pos"
+    echo $name
+    echo $data_dir
+end
+
+# The --data-dir to pass on to candidates, as separate words, if one was given
+function __benchviz_data_dir
+    set -l data_dir (__benchviz_state)[3]
+    if test -n "$data_dir"
+        echo --data-dir
+        string replace -r '^~' $HOME -- $data_dir
+    end
+end
+
+function __benchviz_wants_names
+    contains -- (__benchviz_state)[1] {names}
+end
+
+function __benchviz_wants_runs
+    contains -- (__benchviz_state)[1] {runs}
+end
+
+complete -c benchviz -n __benchviz_wants_names -f \
+    -a "(benchviz (__benchviz_data_dir) candidates names 2>/dev/null)"
+complete -c benchviz -n __benchviz_wants_runs -f \
+    -a "(benchviz (__benchviz_data_dir) candidates runs (__benchviz_state)[2] 2>/dev/null)"
+"#,
+        value_flags = VALUE_FLAGS.join(" "),
+        names = name_states().join(" "),
+        runs = run_states().join(" ")
+    )
+}
@@ -0,0 +1,256 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing the asymptotic complexity of benchmark families, such as
+// BM_Sort/8, BM_Sort/64, ..., either as google/benchmark reported it for those
+// registered with ->Complexity(), or fitted here from the results themselves.
+
+use serde::Serialize;
+
+use crate::metric::Metric;
+use crate::name::BenchName;
+use crate::types::BenchResult;
+
+// Fewer distinct sizes than this can't tell the classes apart
+pub const MIN_POINTS: usize = 3;
+
+// In order of growth, so a later class is a worse one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Complexity {
+    Constant,
+    Logarithmic,
+    Linear,
+    Linearithmic,
+    Quadratic,
+    Cubic
+}
+
+const CLASSES: &[Complexity] = &[
+    Complexity::Constant,
+    Complexity::Logarithmic,
+    Complexity::Linear,
+    Complexity::Linearithmic,
+    Complexity::Quadratic,
+    Complexity::Cubic
+];
+
+impl Complexity {
+    pub fn name(&self) -> &str {
+        match self {
+            Complexity::Constant => "O(1)",
+            Complexity::Logarithmic => "O(lgN)",
+            Complexity::Linear => "O(N)",
+            Complexity::Linearithmic => "O(NlgN)",
+            Complexity::Quadratic => "O(N^2)",
+            Complexity::Cubic => "O(N^3)"
+        }
+    }
+
+    // From the big_o google/benchmark reports, None for a user supplied f(N)
+    pub fn parse(big_o: &str) -> Option<Complexity> {
+        match big_o {
+            "(1)" | "1" => Some(Complexity::Constant),
+            "lgN" => Some(Complexity::Logarithmic),
+            "N" => Some(Complexity::Linear),
+            "NlgN" => Some(Complexity::Linearithmic),
+            "N^2" => Some(Complexity::Quadratic),
+            "N^3" => Some(Complexity::Cubic),
+            _ => None
+        }
+    }
+
+    fn scale(&self, n: f64) -> f64 {
+        match self {
+            Complexity::Constant => 1.0,
+            Complexity::Logarithmic => n.log2(),
+            Complexity::Linear => n,
+            Complexity::Linearithmic => n * n.log2(),
+            Complexity::Quadratic => n * n,
+            Complexity::Cubic => n * n * n
+        }
+    }
+}
+
+impl serde::Serialize for Complexity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Fit {
+    pub complexity:  Complexity,
+    // Time is roughly coefficient * complexity(N)
+    pub coefficient: f64,
+    // Root mean square error of the fit, as a percentage of the mean time
+    pub rms_percent: f64
+}
+
+impl Fit {
+    pub fn estimate(&self, n: f64) -> f64 {
+        self.coefficient * self.complexity.scale(n)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FamilyComplexity {
+    // The benchmark names without their size, e.g. BM_Sort for BM_Sort/8
+    pub name:     String,
+    pub unit:     String,
+    // Size and value, one per size in increasing order
+    pub points:   Vec<(f64, f64)>,
+    pub fitted:   Option<Fit>,
+    // As google/benchmark computed it, from the size the benchmark set with SetComplexityN
+    pub reported: Option<Fit>
+}
+
+impl FamilyComplexity {
+    // The library's own is preferred, as it knows what N really was
+    pub fn complexity(&self) -> Option<Complexity> {
+        self.reported.as_ref().or(self.fitted.as_ref()).map(|fit| fit.complexity)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ComplexityChange {
+    pub name: String,
+    pub from: Complexity,
+    pub to:   Complexity
+}
+
+// The class fitting best by least squares, as google/benchmark fits them. None without
+// enough distinct sizes.
+pub fn fit(points: &[(f64, f64)]) -> Option<Fit> {
+    if points.len() < MIN_POINTS {
+        return None;
+    }
+
+    let mean = points.iter().map(|(_, value)| value).sum::<f64>() / points.len() as f64;
+    CLASSES
+        .iter()
+        .map(|complexity| {
+            let scaled: Vec<(f64, f64)> = points.iter().map(|(n, value)| (complexity.scale(*n), *value)).collect();
+            let coefficient = scaled.iter().map(|(scale, value)| scale * value).sum::<f64>()
+                / scaled.iter().map(|(scale, _)| scale * scale).sum::<f64>();
+            let squares = scaled.iter().map(|(scale, value)| (value - coefficient * scale).powi(2)).sum::<f64>();
+            let rms_percent = 100.0 * (squares / points.len() as f64).sqrt() / mean;
+            Fit { complexity: *complexity, coefficient, rms_percent }
+        })
+        .filter(|fit| fit.coefficient.is_finite() && fit.rms_percent.is_finite())
+        .min_by(|a, b| a.rms_percent.partial_cmp(&b.rms_percent).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+// What google/benchmark reported for a family, from its _BigO and _RMS entries
+fn reported(results: &[BenchResult], family: &str, metric: &Metric) -> Option<Fit> {
+    let aggregate = |kind: &str| {
+        results.iter().find(|result| {
+            let name = BenchName::parse(&result.name);
+            name.aggregate.as_deref() == Some(kind) && BenchName { aggregate: None, ..name }.to_string() == family
+        })
+    };
+    let big_o = aggregate("BigO")?;
+    let coefficient = match metric {
+        Metric::RealTime => "real_coefficient",
+        Metric::CpuTime => "cpu_coefficient",
+        _ => return None
+    };
+
+    Some(Fit {
+        complexity:  Complexity::parse(big_o.extra.get("big_o")?.as_str()?)?,
+        coefficient: big_o.extra.get(coefficient)?.as_f64()?,
+        rms_percent: 100.0 * aggregate("RMS").and_then(|rms| rms.extra.get("rms")?.as_f64()).unwrap_or(f64::NAN)
+    })
+}
+
+// The complexity of every family of a run with enough sizes to fit, or which
+// google/benchmark reported one for, in the order they first appear. N is the first
+// unnamed argument, so BM_Copy/64/threads:8 is N=64 of BM_Copy/threads:8.
+pub fn complexities(results: &[BenchResult], metric: &Metric) -> Vec<FamilyComplexity> {
+    let table = match crate::name::pivot(results, "arg0", metric) {
+        Some(table) => table,
+        None => return Vec::new()
+    };
+
+    table
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let points = table.points(row);
+            let (fitted, reported) = (fit(&points), reported(results, &row.name, metric));
+            let family = FamilyComplexity { name: row.name.clone(), unit: table.unit.clone(), points, fitted, reported };
+            Some(family).filter(|family| family.complexity().is_some())
+        })
+        .collect()
+}
+
+// Families whose class differs between two runs
+pub fn changes(lhs: &[FamilyComplexity], rhs: &[FamilyComplexity]) -> Vec<ComplexityChange> {
+    lhs.iter()
+        .filter_map(|before| {
+            let after = rhs.iter().find(|after| after.name == before.name)?;
+            let (from, to) = (before.complexity()?, after.complexity()?);
+            Some(ComplexityChange { name: before.name.clone(), from, to }).filter(|_| from != to)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complexity::{changes, complexities, fit, Complexity};
+    use crate::metric::Metric;
+    use crate::types::BenchResult;
+
+    #[test]
+    fn check_fit() {
+        let points = |f: &dyn Fn(f64) -> f64| -> Vec<(f64, f64)> {
+            [8.0, 64.0, 512.0, 4096.0].iter().map(|n| (*n, f(*n) * (1.0 + 0.02 * (n.log2() % 2.0)))).collect()
+        };
+        assert_eq!(fit(&points(&|_| 5.0)).unwrap().complexity, Complexity::Constant);
+        assert_eq!(fit(&points(&|n| 3.0 * n)).unwrap().complexity, Complexity::Linear);
+        assert_eq!(fit(&points(&|n| n * n.log2())).unwrap().complexity, Complexity::Linearithmic);
+        assert_eq!(fit(&points(&|n| 0.5 * n * n)).unwrap().complexity, Complexity::Quadratic);
+        assert!((fit(&points(&|n| 3.0 * n)).unwrap().coefficient - 3.0).abs() < 0.1);
+        assert!(fit(&[(8.0, 1.0), (64.0, 8.0)]).is_none());
+    }
+
+    #[test]
+    fn check_complexities() {
+        let run = |square: bool| -> Vec<BenchResult> {
+            let mut results: Vec<BenchResult> = [8, 64, 512, 4096]
+                .iter()
+                .map(|n| BenchResult {
+                    cpu_time: 1.0,
+                    ..BenchResult::timed(&format!("BM_Find/{}", n), if square { (n * n) as f64 } else { *n as f64 })
+                })
+                .collect();
+            // A ->Complexity() benchmark, as google/benchmark writes it
+            for aggregate in [
+                serde_json::json!({"name": "BM_Hash/8", "iterations": 1, "real_time": 9.0, "cpu_time": 9.0, "time_unit": "ns"}),
+                serde_json::json!({"name": "BM_Hash_BigO", "run_name": "BM_Hash", "run_type": "aggregate",
+                                   "aggregate_name": "BigO", "cpu_coefficient": 1.5, "real_coefficient": 1.25,
+                                   "big_o": "lgN", "time_unit": "ns"}),
+                serde_json::json!({"name": "BM_Hash_RMS", "run_name": "BM_Hash", "run_type": "aggregate",
+                                   "aggregate_name": "RMS", "rms": 0.03}),
+            ] {
+                results.push(serde_json::from_value(aggregate).unwrap());
+            }
+            results
+        };
+
+        let before = complexities(&run(false), &Metric::RealTime);
+        let names: Vec<&str> = before.iter().map(|family| family.name.as_str()).collect();
+        assert_eq!(names, vec!["BM_Find", "BM_Hash"]);
+        assert_eq!(before[0].complexity(), Some(Complexity::Linear));
+        assert!(before[1].fitted.is_none());
+        assert_eq!(before[1].complexity(), Some(Complexity::Logarithmic));
+        assert_eq!(before[1].reported.as_ref().unwrap().coefficient, 1.25);
+        assert!((before[1].reported.as_ref().unwrap().rms_percent - 3.0).abs() < 1e-9);
+        // Aggregates aren't results to compare
+        assert_eq!(Metric::RealTime.value(&run(false)[5]), None);
+
+        let after = complexities(&run(true), &Metric::RealTime);
+        let changed = changes(&before, &after);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].name, "BM_Find");
+        assert_eq!((changed[0].from, changed[0].to), (Complexity::Linear, Complexity::Quadratic));
+    }
+}
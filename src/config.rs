@@ -3,110 +3,122 @@
 // Module containing config functionality, including fetching the
 // contents of the config file, as well as sub-config info.
 
-use std::fs;
+use std::sync::OnceLock;
 
-use crate::types::{IndividualBenchInfo, TopLevelBenchInfo};
+use crate::error::{Error, Result};
+use crate::workspace::{Workspace, WorkspaceLock, LOCAL_WORKSPACE_DIR};
 
-pub fn get_config_root_dir() -> std::path::PathBuf {
-    match dirs::config_dir() {
-        Some(root_dir) => {
-            let config_dir = root_dir.join(std::path::Path::new("bb/"));
-            println!("Using config directory at {:?}", config_dir);
-            config_dir
-        }
-        None => {
-            println!("Failed to create config directory");
-            std::process::exit(1);
-        }
+static WORKSPACE: OnceLock<Workspace> = OnceLock::new();
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
+    // Only compare has a markdown rendering, everything else prints text
+    Markdown
+}
+
+impl OutputFormat {
+    pub fn is_structured(self) -> bool {
+        self == OutputFormat::Json || self == OutputFormat::Yaml
     }
 }
 
-pub fn get_individual_config_file(name: &str) -> std::path::PathBuf {
-    get_config_root_dir().join(name.to_string()).join("info.json")
+// Use the given directory for all config, regardless of environment. This must
+// happen before anything reads the config.
+pub fn set_config_root_dir(dir: &str) -> Result<()> {
+    match WORKSPACE.set(Workspace::open(dir)) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(Error::Invalid(format!("Config directory was already chosen before {:?} was given", dir)))
+    }
 }
 
-pub fn get_top_level_config_file() -> std::path::PathBuf {
-    get_config_root_dir().join("top.json")
+// How the read commands should print their results, text unless told otherwise.
+pub fn set_output_format(format: &str) {
+    let format = match format {
+        "json" => OutputFormat::Json,
+        "yaml" => OutputFormat::Yaml,
+        "markdown" => OutputFormat::Markdown,
+        _ => OutputFormat::Text
+    };
+    let _ = OUTPUT_FORMAT.set(format);
 }
 
-pub fn ensure_dependencies_available() {
-    if !crate::git::is_available() {
-        println!("Failed to find dependency \"git\"");
-        std::process::exit(1);
-    }
+pub fn output_format() -> OutputFormat {
+    *OUTPUT_FORMAT.get_or_init(|| OutputFormat::Text)
 }
 
-pub fn ensure_initialized() {
-    // Create our directory structure if needed
-    let dir = get_config_root_dir().to_owned();
-    if !dir.exists() {
-        let config_msg = format!("There is no config directory for bb, can I create one at {}?", &dir.to_string_lossy());
-        if dialoguer::Confirmation::new().with_text(&config_msg).interact().is_err() {
-            println!("Ok, exiting simulation.");
-            return;
-        } else if std::fs::create_dir(&dir).is_err() {
-            println!("Failed to create new directory at {:?}", dir);
-            return;
-        }
+// In order of preference: --data-dir, $BENCHVIZ_HOME, a .benchviz directory in
+// the current directory or any of its parents, then the global config directory.
+pub fn workspace() -> Result<&'static Workspace> {
+    if let Some(workspace) = WORKSPACE.get() {
+        return Ok(workspace);
     }
 
-    // Create our top level config files if needed
-    let config_file = get_top_level_config_file();
-    if !config_file.exists() {
-        let empty_bench_info = TopLevelBenchInfo::new();
-        if std::fs::File::create(&config_file).is_err() {
-            println!("Failed to create config file");
-            return;
-        }
-
-        println!("Writing default config file at {:?}", config_file);
-        fs::write(&config_file, serde_json::to_string_pretty(&empty_bench_info).unwrap()).unwrap();
+    match Workspace::locate() {
+        Some(workspace) => Ok(WORKSPACE.get_or_init(|| workspace)),
+        None => Err(Error::Invalid("Unable to find a config directory, try --data-dir or $BENCHVIZ_HOME".to_string()))
     }
 }
 
-pub fn read_top_level_config() -> TopLevelBenchInfo {
-    let config_file = get_top_level_config_file();
-    if !config_file.is_file() && std::fs::File::create(&config_file).is_err() {
-        println!("Failed to create config file");
-        std::process::exit(1);
+// Create a project local workspace in the current directory
+pub fn init_local_workspace() -> Result<()> {
+    let dir = std::env::current_dir().map_err(Error::io("Failed to find the current directory"))?.join(LOCAL_WORKSPACE_DIR);
+    if dir.exists() {
+        println!("A workspace already exists at {:?}", dir);
+        return Ok(());
     }
 
-    let contents = std::fs::read(&config_file);
-    if contents.is_err() {
-        println!("Failed to read config file contents");
-        std::process::exit(1);
-    }
+    Workspace::init(&dir)?;
+
+    // Only the catalog itself belongs under version control
+    let ignore = dir.join(".gitignore");
+    std::fs::write(&ignore, ".lock\n*.tmp\n*.bak\n").map_err(Error::io(format!("Failed to write {:?}", ignore)))?;
+    println!("Created a workspace at {:?}, benchviz will use it from here and any subdirectory.", dir);
+    Ok(())
+}
 
-    type ConfigParseResult = Result<TopLevelBenchInfo, serde_json::Error>;
-    let utf8_contents = String::from_utf8_lossy(&contents.unwrap()).to_string();
-    let benches: ConfigParseResult = serde_json::from_str(&utf8_contents);
-    if benches.is_err() {
-        println!("Failed to parse json from config");
-        std::process::exit(1);
+pub fn ensure_dependencies_available() -> Result<()> {
+    if !crate::git::is_available() {
+        return Err(Error::Git("Failed to find dependency \"git\"".to_string()));
     }
 
-    benches.unwrap()
+    Ok(())
 }
 
-pub fn read_individual_config(name: &str) -> IndividualBenchInfo {
-    let config_file = get_individual_config_file(name);
-    if !config_file.is_file() {
-        println!("Unable to find expected config file: {:?}", config_file);
-        std::process::exit(1);
+pub fn ensure_initialized() -> Result<()> {
+    // Create our directory structure if needed
+    let workspace = workspace()?;
+    let dir = workspace.root();
+    if !dir.exists() {
+        let config_msg = format!("There is no config directory for bb, can I create one at {}?", &dir.to_string_lossy());
+        if !dialoguer::Confirmation::new().with_text(&config_msg).interact().unwrap_or(false) {
+            eprintln!("Ok, exiting simulation.");
+            return Err(Error::ConfigMissing(dir.to_owned()));
+        }
     }
 
-    let contents = std::fs::read(&config_file);
-    if contents.is_err() {
-        println!("Failed to read config file contents");
+    // Create our top level config files if needed. Notes like this go to stderr, where they
+    // can't corrupt --format json or yaml output.
+    let config_file = workspace.top_level_file();
+    if !config_file.exists() {
+        eprintln!("Writing default config file at {:?}", config_file);
     }
 
-    type ConfigParseResult = Result<IndividualBenchInfo, serde_json::Error>;
-    let utf8_contents = String::from_utf8_lossy(&contents.unwrap()).to_string();
-    let benches: ConfigParseResult = serde_json::from_str(&utf8_contents);
-    if benches.is_err() {
-        println!("Failed to parse json from config");
-        std::process::exit(1);
-    }
+    Workspace::init(dir)?;
+    Ok(())
+}
 
-    benches.unwrap()
+// Take the workspace lock, waiting for any other benchviz process that holds it.
+pub fn lock_workspace() -> Result<WorkspaceLock> {
+    let workspace = workspace()?;
+    match workspace.try_lock()? {
+        Some(lock) => Ok(lock),
+        None => {
+            eprintln!("Waiting for another benchviz process to finish with {:?}...", workspace.root());
+            workspace.lock()
+        }
+    }
 }
@@ -0,0 +1,187 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing functionality for finding google/benchmark executables
+// in a build tree, so they can be registered without typing out paths.
+
+use std::fs;
+use std::io::Read;
+use std::path;
+use std::process;
+use std::thread;
+use std::time;
+
+pub struct Candidate {
+    pub executable: path::PathBuf,
+    pub tests:      Vec<String>
+}
+
+pub struct BuildTree {
+    pub build_dir:   path::PathBuf,
+    pub source_root: path::PathBuf
+}
+
+// Every binary linked against google/benchmark embeds its flag names, so this
+// lets us avoid launching arbitrary executables just to ask them a question.
+const BENCHMARK_MARKER: &[u8] = b"benchmark_list_tests";
+const LIST_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+// Given either a build directory or a source directory, find the CMake build
+// tree (if there is one) and the source root it was configured from.
+pub fn find_build_tree(root: &path::Path) -> BuildTree {
+    let mut build_dirs = vec![root.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(root) {
+        let mut children: Vec<path::PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        children.sort();
+        build_dirs.extend(children.into_iter().filter(|p| p.is_dir()));
+    }
+
+    for dir in build_dirs {
+        if let Some(source_root) = read_cmake_home(&dir.join("CMakeCache.txt")) {
+            return BuildTree { build_dir: dir, source_root };
+        }
+    }
+
+    BuildTree { build_dir: root.to_path_buf(), source_root: root.to_path_buf() }
+}
+
+fn read_cmake_home(cache: &path::Path) -> Option<path::PathBuf> {
+    let contents = fs::read_to_string(cache).ok()?;
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("CMAKE_HOME_DIRECTORY:INTERNAL="))
+        .map(|dir| path::PathBuf::from(dir.trim()))
+}
+
+// Whether an executable is already the source_bin of a registered benchmark, however
+// either path was spelt
+pub fn is_registered(executable: &path::Path, source_bins: &[&str]) -> bool {
+    let canonical = |path: &path::Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let executable = canonical(executable);
+    source_bins.iter().any(|bin| canonical(path::Path::new(bin)) == executable)
+}
+
+pub fn find_benchmarks(build_dir: &path::Path) -> Vec<Candidate> {
+    let mut executables = vec![];
+    collect_executables(build_dir, &mut executables);
+    executables.sort();
+
+    executables
+        .into_iter()
+        .filter(|exe| links_benchmark(exe))
+        .filter_map(|exe| list_tests(&exe).map(|tests| Candidate { executable: exe, tests }))
+        .collect()
+}
+
+fn collect_executables(dir: &path::Path, found: &mut Vec<path::PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if file_name != "CMakeFiles" && !file_name.starts_with('.') {
+                collect_executables(&path, found);
+            }
+        } else if is_executable(&path) {
+            found.push(path);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false
+    }
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &path::Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "exe")
+}
+
+fn links_benchmark(exe: &path::Path) -> bool {
+    match fs::read(exe) {
+        Ok(contents) => contents.windows(BENCHMARK_MARKER.len()).any(|w| w == BENCHMARK_MARKER),
+        Err(_) => false
+    }
+}
+
+// Ask the executable which benchmarks it contains, giving up on anything that
+// hangs or fails rather than blocking discovery.
+fn list_tests(exe: &path::Path) -> Option<Vec<String>> {
+    let mut child = process::Command::new(exe)
+        .arg("--benchmark_list_tests=true")
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut raw = String::new();
+        stdout.read_to_string(&mut raw).map(|_| raw)
+    });
+
+    let start = time::Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if start.elapsed() < LIST_TIMEOUT => thread::sleep(time::Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+    };
+
+    let raw = reader.join().ok()?.ok()?;
+    let tests: Vec<String> = raw.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+    match status {
+        Some(status) if status.success() && !tests.is_empty() => Some(tests),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discover::{find_build_tree, is_registered, read_cmake_home};
+    use std::fs;
+
+    #[test]
+    fn check_find_build_tree() {
+        let root = std::env::temp_dir().join(format!("benchviz-discover-{}", std::process::id()));
+        let build = root.join("build");
+        fs::create_dir_all(&build).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        let cache = "# comment\nCMAKE_BUILD_TYPE:STRING=Release\nCMAKE_HOME_DIRECTORY:INTERNAL=/src/project \n";
+        fs::write(build.join("CMakeCache.txt"), cache).unwrap();
+
+        assert_eq!(read_cmake_home(&build.join("CMakeCache.txt")), Some(std::path::PathBuf::from("/src/project")));
+        assert_eq!(read_cmake_home(&root.join("CMakeCache.txt")), None);
+
+        // From the source directory, the build tree is found among its children
+        let tree = find_build_tree(&root);
+        assert_eq!(tree.build_dir, build);
+        assert_eq!(tree.source_root, std::path::PathBuf::from("/src/project"));
+        // And given the build tree itself
+        assert_eq!(find_build_tree(&build).build_dir, build);
+        // Without a CMakeCache.txt anywhere, the directory given is both
+        let docs = find_build_tree(&root.join("docs"));
+        assert_eq!((docs.build_dir.clone(), docs.source_root), (root.join("docs"), root.join("docs")));
+
+        let bench = build.join("bench_sort");
+        fs::write(&bench, "").unwrap();
+        let spelt = root.join("docs").join("..").join("build").join("bench_sort");
+        assert!(is_registered(&bench, &[spelt.to_str().unwrap()]));
+        assert!(!is_registered(&bench, &["/usr/bin/bench_sort"]));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
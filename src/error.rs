@@ -0,0 +1,90 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing the error type shared by everything that can fail, so
+// callers can tell what went wrong without scraping messages, and the CLI can
+// report each kind with its own exit code.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::types::TopLevelBenchInfo;
+
+#[derive(Debug)]
+pub enum Error {
+    // Nothing usable was asked for, e.g. a malformed tag or a name already taken
+    Invalid(String),
+    // A workspace, or a file that should be in one, doesn't exist
+    ConfigMissing(PathBuf),
+    Parse { what: String, message: String },
+    UnknownBenchmark { name: String, suggestions: Vec<String> },
+    InvalidRunId(String),
+    Git(String),
+    Subprocess { command: String, message: String },
+    Io { context: String, error: io::Error }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    // For map_err, saying what was being attempted when an io error occurred
+    pub fn io<C: fmt::Display>(context: C) -> impl FnOnce(io::Error) -> Error {
+        move |error| Error::Io { context: context.to_string(), error }
+    }
+
+    pub fn parse<W: fmt::Display, M: fmt::Display>(what: W, message: M) -> Error {
+        Error::Parse { what: what.to_string(), message: message.to_string() }
+    }
+
+    // An unknown name, along with the closest names that are known
+    pub fn unknown_benchmark(name: &str, benches: &TopLevelBenchInfo) -> Error {
+        let names: Vec<&str> = benches.keys().map(|id| id.as_str()).collect();
+        let suggestions = crate::fuzzy::rank(name, &names).iter().take(5).map(|s| s.to_string()).collect();
+        Error::UnknownBenchmark { name: name.to_string(), suggestions }
+    }
+
+    // Process exit codes, stable so that scripts can act on them. Usage errors
+    // share 1 with the argument parser.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Invalid(_) => 1,
+            Error::ConfigMissing(_) => 2,
+            Error::Parse { .. } => 3,
+            Error::UnknownBenchmark { .. } => 4,
+            Error::InvalidRunId(_) => 5,
+            Error::Git(_) => 6,
+            Error::Subprocess { .. } => 7,
+            Error::Io { .. } => 8
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Invalid(message) => write!(f, "{}", message),
+            Error::ConfigMissing(path) => write!(f, "Expected config {:?} does not exist", path),
+            Error::Parse { what, message } => write!(f, "Failed to parse {}: {}", what, message),
+            Error::UnknownBenchmark { name, suggestions } if suggestions.is_empty() => {
+                write!(f, "Name {:?} not found in benches", name)
+            }
+            Error::UnknownBenchmark { name, suggestions } => {
+                let quoted: Vec<String> = suggestions.iter().map(|s| format!("{:?}", s)).collect();
+                write!(f, "Name {:?} not found in benches, did you mean {}?", name, quoted.join(", "))
+            }
+            Error::InvalidRunId(message) => write!(f, "{}", message),
+            Error::Git(message) => write!(f, "git failed: {}", message),
+            Error::Subprocess { command, message } => write!(f, "Failed to run {}: {}", command, message),
+            Error::Io { context, error } => write!(f, "{}: {}", context, error)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
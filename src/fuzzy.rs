@@ -0,0 +1,85 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing fuzzy matching of benchmark names, so that typos and
+// partial names can be resolved (or at least suggested) instead of failing.
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
+pub fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+// The candidate starting with query, if exactly one does.
+pub fn unique_prefix<'a>(query: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let mut matches = candidates.iter().filter(|c| c.starts_with(query));
+    match (matches.next(), matches.next()) {
+        (Some(only), None) => Some(only),
+        _ => None
+    }
+}
+
+// Candidates resembling the query, best match first. Prefixes beat substrings,
+// which beat subsequences, which beat anything merely within a few edits.
+pub fn rank<'a>(query: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+    let max_distance = std::cmp::max(2, query.chars().count() / 3);
+    let mut scored: Vec<(usize, usize, &str)> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let distance = levenshtein(&query, &lower);
+            let class = if lower.starts_with(&query) {
+                0
+            } else if lower.contains(&query) {
+                1
+            } else if is_subsequence(&query, &lower) {
+                2
+            } else if distance <= max_distance {
+                3
+            } else {
+                return None;
+            };
+            Some((class, distance, *candidate))
+        })
+        .collect();
+
+    scored.sort();
+    scored.into_iter().map(|(_, _, candidate)| candidate).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuzzy::{is_subsequence, levenshtein, rank, unique_prefix};
+
+    #[test]
+    fn check_levenshtein() {
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("same", "same"), 0);
+    }
+
+    #[test]
+    fn check_matching() {
+        let names = ["hashmap_std", "hashmap_abseil", "vector_push"];
+        assert!(is_subsequence("hmstd", "hashmap_std"));
+        assert_eq!(unique_prefix("vec", &names), Some("vector_push"));
+        assert_eq!(unique_prefix("hashmap", &names), None);
+        assert_eq!(rank("hashmap_sdt", &names).first(), Some(&"hashmap_std"));
+        assert_eq!(rank("absl", &names), vec!["hashmap_abseil"]);
+        assert!(rank("zzz", &names).is_empty());
+    }
+}
@@ -5,41 +5,54 @@
 
 use std::process;
 
+use crate::error::{Error, Result};
+
 pub fn is_available() -> bool {
     process::Command::new("git").output().is_ok()
 }
 
-pub fn diff(source_root: &str, hash1: &str, hash2: &str) -> String {
-    if !is_available() {
-        return "".to_string();
-    }
+// Run git in the source root, returning what it printed, or why it failed
+fn run(source_root: &str, args: &[&str]) -> Result<String> {
+    let output = match process::Command::new("git").args(args).current_dir(source_root).output() {
+        Ok(output) => output,
+        Err(e) => return Err(Error::Git(format!("unable to run git in {:?}: {}", source_root, e)))
+    };
 
-    match std::env::current_dir() {
-        Ok(curr) => {
-            std::env::set_current_dir(&source_root).unwrap();
-            let output = process::Command::new("git").arg("diff").arg("--color=always").arg(&hash1).arg(&hash2).output().unwrap();
-            let raw: String = String::from_utf8_lossy(&output.stdout).to_string();
-            std::env::set_current_dir(&curr).unwrap();
-            raw.to_string()
-        }
-        _ => "".to_string()
+    if !output.status.success() {
+        return Err(Error::Git(String::from_utf8_lossy(&output.stderr).trim().to_string()));
     }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn diff(source_root: &str, hash1: &str, hash2: &str, color: bool) -> Result<String> {
+    let color = if color { "--color=always" } else { "--color=never" };
+    run(source_root, &["diff", color, hash1, hash2])
 }
 
-pub fn hash(source_root: &str) -> String {
+pub fn hash(source_root: &str) -> Result<String> {
+    run(source_root, &["rev-parse", "HEAD"]).map(|raw| raw.trim().to_string())
+}
+
+// Resolve a ref (branch, tag, HEAD~2, ...) to the full hash of the commit it names
+pub fn rev_parse(source_root: &str, rev: &str) -> Option<String> {
     if !is_available() {
-        return "".to_string();
+        return None;
     }
 
-    match std::env::current_dir() {
-        Ok(curr) => {
-            std::env::set_current_dir(&source_root).unwrap();
-            let output = process::Command::new("git").arg("rev-parse").arg("HEAD").output().unwrap();
-            let raw: String = String::from_utf8_lossy(&output.stdout).to_string();
-            std::env::set_current_dir(&curr).unwrap();
-            raw.trim().to_string()
-        }
-        _ => "".to_string()
+    let output = process::Command::new("git")
+        .arg("rev-parse")
+        .arg("--verify")
+        .arg("--quiet")
+        .arg(format!("{}^{{commit}}", rev))
+        .current_dir(source_root)
+        .output()
+        .ok()?;
+    let raw: String = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !raw.is_empty() {
+        Some(raw)
+    } else {
+        None
     }
 }
 
@@ -53,15 +66,24 @@ mod tests {
     #[test]
     fn check_hash() {
         let curr = std::env::current_dir().unwrap();
-        let hash = crate::git::hash(&curr.to_str().unwrap());
-        assert!(hash.len() > 0);
+        let hash = crate::git::hash(curr.to_str().unwrap()).unwrap();
+        assert!(!hash.is_empty());
+        assert!(crate::git::hash("/no/such/directory").is_err());
     }
 
     #[test]
     fn check_diff() {
         let curr = std::env::current_dir().unwrap();
-        let hash = crate::git::hash(&curr.to_str().unwrap());
-        let diff = crate::git::diff(&curr.to_str().unwrap(), &hash, &hash);
-        assert!(diff.len() == 0);
+        let hash = crate::git::hash(curr.to_str().unwrap()).unwrap();
+        let diff = crate::git::diff(curr.to_str().unwrap(), &hash, &hash, true).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn check_rev_parse() {
+        let curr = std::env::current_dir().unwrap();
+        let hash = crate::git::hash(curr.to_str().unwrap()).unwrap();
+        assert_eq!(crate::git::rev_parse(curr.to_str().unwrap(), "HEAD"), Some(hash));
+        assert_eq!(crate::git::rev_parse(curr.to_str().unwrap(), "no-such-ref-exists"), None);
     }
 }
@@ -7,81 +7,209 @@ use std::fs;
 use std::path;
 use std::process;
 
-use prettytable::{cell, row};
+use crate::error::{Error, Result};
 
 fn lookup_benchmark(name: &str) -> crate::types::BenchmarkQuery {
-    let benches = crate::config::read_top_level_config();
-    match benches.get(name) {
-        Some(header) => Some((header.clone(), crate::config::read_individual_config(name))),
-        None => {
-            use colored::*;
-            println!("{}", format!("> Name {:?} not found in benches.", name).red());
-            println!("{}", "  Currently available benchmarks".red());
-            let benches = crate::config::read_top_level_config();
-            for (id, _) in benches {
-                let fmt = format!("  > Name: {:?}", id);
-                println!("{}", fmt.red());
+    let workspace = crate::config::workspace()?;
+    Ok((workspace.header(name)?, workspace.runs(name)?))
+}
+
+// Errors go to stderr, so they never end up mixed into output meant for other tools.
+pub fn print_error(error: &Error) {
+    use colored::*;
+    match error {
+        Error::UnknownBenchmark { name, suggestions } => {
+            eprintln!("{}", format!("> Name {:?} not found in benches.", name).red());
+            if suggestions.is_empty() {
+                eprintln!("{}", "  Use the list command to see the available benchmarks.".red());
+            } else {
+                eprintln!("{}", "  Did you mean".red());
+                for suggestion in suggestions {
+                    eprintln!("{}", format!("  > Name: {:?}", suggestion).red());
+                }
             }
-            None
         }
+        _ => eprintln!("{}", format!("Error: {}", error).red())
     }
 }
 
-pub fn parse_run_id(name: &str, run_id: &str) -> Option<crate::types::RunId> {
-    let info = crate::config::read_individual_config(&name);
+// Print a report as json or yaml, for --format
+fn print_structured<T: serde::Serialize>(report: &T) -> Result<()> {
+    let printed = match crate::config::output_format() {
+        crate::config::OutputFormat::Yaml => serde_yaml::to_string(report).map_err(|e| Error::parse("the report", e))?,
+        _ => serde_json::to_string_pretty(report).map_err(|e| Error::parse("the report", e))?
+    };
 
-    if run_id == "*" {
-        return Some(crate::types::RunId::All);
+    println!("{}", printed.trim_end());
+    Ok(())
+}
+
+// Terminal prompts only fail when there is no terminal to prompt on
+fn interact<T>(result: std::io::Result<T>) -> Result<T> {
+    result.map_err(Error::io("Failed to prompt on the terminal"))
+}
+
+// The benchmark named exactly, for commands which change a benchmark's data, so a typo or
+// a short prefix can't remove or rewrite one the user never typed.
+pub fn exact_benchmark_name(name: &str) -> Result<String> {
+    let benches = crate::config::workspace()?.headers()?;
+    if benches.contains_key(name) {
+        Ok(name.to_string())
     } else {
-        match run_id.parse::<usize>() {
-            Ok(val) => {
-                let len_benches = info.benchmarks.len();
-                let len_comments = info.commentary.len();
-                let len_hashes = info.commentary.len();
-
-                if len_benches != len_comments || len_comments != len_hashes {
-                    println!("Benchmarks file in inconsistent state, perhaps it was hand edited?");
-                    std::process::exit(1);
-                }
+        Err(Error::unknown_benchmark(name, &benches))
+    }
+}
 
-                if val >= len_benches {
-                    println!("Invalid run id specified, try again");
-                }
+// Resolve a benchmark name given by the user, accepting any unique prefix of a real name.
+// Only for commands which just read a benchmark.
+pub fn resolve_benchmark_name(name: &str) -> Result<String> {
+    let benches = crate::config::workspace()?.headers()?;
+    if benches.contains_key(name) {
+        return Ok(name.to_string());
+    }
 
-                return Some(crate::types::RunId::Index(val));
-            }
-            Err(_) => {
-                println!("Unparseable unsigned supplied, try again.");
+    let names: Vec<&str> = benches.keys().map(|id| id.as_str()).collect();
+    match crate::fuzzy::unique_prefix(name, &names) {
+        Some(resolved) => Ok(resolved.to_string()),
+        None => Err(Error::unknown_benchmark(name, &benches))
+    }
+}
+
+pub fn parse_run_id(name: &str, run_id: &str) -> Result<crate::types::RunId> {
+    let (header, info) = lookup_benchmark(name)?;
+    let selector = match crate::selector::parse(run_id) {
+        Some(selector) => selector,
+        None => return Err(Error::InvalidRunId(format!("Unparseable run id {:?} supplied.", run_id)))
+    };
+
+    match selector {
+        crate::selector::Selector::All => Ok(crate::types::RunId::All),
+        crate::selector::Selector::Range(start, end, inclusive) => {
+            let (start, end) = resolve_range(&header, &info, start.as_deref(), end.as_deref(), inclusive)?;
+            if start >= end {
+                return Err(Error::InvalidRunId(format!("Run range {:?} is empty.", run_id)));
             }
+
+            Ok(crate::types::RunId::Range(start, end))
         }
+        single => resolve_run(&header, &info, &single).map(crate::types::RunId::Index)
     }
+}
+
+// Resolve the ends of a range to the bounds of the runs it covers, end exclusive.
+fn resolve_range(
+    header: &crate::types::BenchHeader,
+    info: &crate::types::IndividualBenchInfo,
+    start: Option<&crate::selector::Selector>,
+    end: Option<&crate::selector::Selector>,
+    inclusive: bool
+) -> Result<(usize, usize)>
+{
+    let num_runs = info.benchmarks.len();
+    let start = match start {
+        Some(start) => resolve_run(header, info, start)?,
+        None => 0
+    };
+    let end = match end {
+        // An exclusive end is one past the last run it covers, so it may be the run count
+        Some(crate::selector::Selector::Index(index)) if !inclusive && *index == num_runs as i64 => num_runs,
+        Some(end) => resolve_run(header, info, end)? + if inclusive { 1 } else { 0 },
+        None => num_runs
+    };
+    Ok((start, end))
+}
+
+// Resolve a selector naming a single run to its index, if it names a recorded run.
+fn resolve_run(
+    header: &crate::types::BenchHeader,
+    info: &crate::types::IndividualBenchInfo,
+    selector: &crate::selector::Selector
+) -> Result<usize>
+{
+    let num_runs = info.benchmarks.len() as i64;
+    let resolved = match selector {
+        crate::selector::Selector::Index(index) => {
+            let index = if *index < 0 { num_runs + index } else { *index };
+            if index >= 0 && index < num_runs {
+                Some(index as usize)
+            } else {
+                // A hash prefix of only digits is read as an index, so say how to give it
+                return Err(Error::InvalidRunId(format!(
+                    "Invalid run id specified ({}), only {} runs recorded. Use git:<hash> for a revision.",
+                    index, num_runs
+                )));
+            }
+        }
+        // Runs whose date can't be read are never picked by one
+        crate::selector::Selector::Date(_, before) => {
+            info.dates.iter().rposition(|date| crate::selector::parse_date(date, false).is_some_and(|date| date <= *before))
+        }
+        crate::selector::Selector::Tag(key, value) => {
+            info.tags.iter().rposition(|tags| tags.get(key).map(|v| v.as_str()) == Some(value.as_str()))
+        }
+        crate::selector::Selector::Revision(rev) => {
+            // Prefer a hash prefix we've recorded, then ask git what the ref points to
+            let is_hash_prefix = rev.len() >= 4 && rev.chars().all(|c| c.is_ascii_hexdigit());
+            let by_prefix = if is_hash_prefix { info.source_hashes.iter().rposition(|h| h.starts_with(rev.as_str())) } else { None };
+            by_prefix.or_else(|| {
+                let hash = crate::git::rev_parse(&header.source_root, rev)?;
+                info.source_hashes.iter().rposition(|h| *h == hash)
+            })
+        }
+        crate::selector::Selector::All | crate::selector::Selector::Range(_, _, _) => None
+    };
+
+    resolved.ok_or_else(|| Error::InvalidRunId(format!("No recorded run matches {}.", describe_selector(selector))))
+}
 
-    None
+fn describe_selector(selector: &crate::selector::Selector) -> String {
+    match selector {
+        crate::selector::Selector::All => "*".to_string(),
+        crate::selector::Selector::Index(index) => index.to_string(),
+        crate::selector::Selector::Date(date, _) => format!("@{}", date),
+        crate::selector::Selector::Tag(key, value) => format!("tag:{}={}", key, value),
+        crate::selector::Selector::Revision(rev) => rev.to_string(),
+        crate::selector::Selector::Range(_, _, _) => "range".to_string()
+    }
 }
 
-pub fn prompt_benchmark_name() -> String {
-    let prompt = "Which benchmark?";
+pub fn prompt_benchmark_name() -> Result<String> {
+    let benches = crate::config::workspace()?.headers()?;
+    let mut names: Vec<&str> = benches.keys().map(|id| id.as_str()).collect();
+    names.sort();
+    if names.is_empty() {
+        return Err(Error::Invalid("No benchmarks have been created yet, try the new command.".to_string()));
+    }
+
     loop {
-        let name: String = dialoguer::Input::new().with_prompt(&prompt).interact().unwrap();
+        let filter: String = interact(
+            dialoguer::Input::new()
+                .with_prompt("Which benchmark? (type part of a name, or leave empty to list all)")
+                .default(String::new())
+                .show_default(false)
+                .interact()
+        )?;
 
-        if let Some((_, _)) = lookup_benchmark(&name) {
-            return name;
-        } else {
-            // TODO: Refactor all this printing stuff
+        let filter = filter.trim();
+        let matches = if filter.is_empty() { names.clone() } else { crate::fuzzy::rank(filter, &names) };
+        if matches.is_empty() {
             use colored::*;
-            println!("{}", "Name not found in benchmarks".red());
-            println!("{}", "Currently available benchmarks".red());
-            let benches = crate::config::read_top_level_config();
-            for (id, _) in benches {
-                let fmt = format!("> Name: {:?}", id);
-                println!("{}", fmt.red());
-            }
+            println!("{}", format!("No benchmarks resemble {:?}, try again.", filter).red());
+            continue;
+        }
+
+        let mut labels: Vec<String> = matches.iter().map(|name| format!("{}: {}", name, benches[*name].description)).collect();
+        labels.push("(search again)".to_string());
+        let choice = interact(dialoguer::Select::new().items(&labels).default(0).interact())?;
+        if choice < matches.len() {
+            return Ok(matches[choice].to_string());
         }
     }
 }
 
-fn prompt_run_id(name: &str) -> crate::types::RunId {
-    let info = crate::config::read_individual_config(&name);
+// Ask for runs to remove, returning the run id as given once it parses
+fn prompt_run_id(name: &str) -> Result<String> {
+    let info = crate::config::workspace()?.runs(name)?;
 
     loop {
         let num_runs = info.benchmarks.len();
@@ -94,68 +222,556 @@ fn prompt_run_id(name: &str) -> crate::types::RunId {
             println!(" > Run #{}: {}", i, info.commentary[i]);
         }
 
-        let run_id: String = dialoguer::Input::new().with_prompt(&prompt).interact().unwrap();
+        let run_id: String = interact(dialoguer::Input::new().with_prompt(&prompt).interact())?;
 
-        if let Some(parsed_run_id) = parse_run_id(&name, &run_id) {
-            return parsed_run_id;
+        // Keep asking until we're given something we can use
+        match parse_run_id(name, &run_id) {
+            Ok(_) => return Ok(run_id),
+            Err(Error::InvalidRunId(message)) => println!("{} Try again.", message),
+            Err(e) => return Err(e)
         }
     }
 }
 
-pub fn print_comparison(name: &str, run_id_1_wrapped: crate::types::RunId, run_id_2_wrapped: crate::types::RunId) {
-    if let (Some((header, info)), crate::types::RunId::Index(run_id_1), crate::types::RunId::Index(run_id_2)) =
-        (lookup_benchmark(name), run_id_1_wrapped, run_id_2_wrapped)
-    {
-        let num_runs = info.commentary.len();
-        if num_runs == 0 {
-            println!("No runs are currently recorded!");
-            return;
+pub fn print_comparison(
+    name: &str,
+    run_id_1_wrapped: crate::types::RunId,
+    run_id_2_wrapped: crate::types::RunId,
+    options: &crate::compare::CompareOptions
+) -> Result<()>
+{
+    let (run_id_1, run_id_2) = single_runs(run_id_1_wrapped, run_id_2_wrapped)?;
+    let (header, info) = lookup_benchmark(name)?;
+    let comparison = crate::config::workspace()?.compare(name, run_id_1, run_id_2, options.baseline, &options.metrics)?;
+    let compared = crate::report::ComparedBenchmark { name, header: &header, info: &info };
+    present_comparison(&compared, &compared, comparison, options)
+}
+
+// Compare a run of one benchmark with a run of another, such as two implementations
+// of the same thing, matching their results up by name.
+pub fn print_cross_comparison(
+    lhs_name: &str,
+    lhs_run_id: crate::types::RunId,
+    rhs_name: &str,
+    rhs_run_id: crate::types::RunId,
+    options: &crate::compare::CompareOptions
+) -> Result<()>
+{
+    let (lhs_run, rhs_run) = single_runs(lhs_run_id, rhs_run_id)?;
+    let (lhs_header, lhs_info) = lookup_benchmark(lhs_name)?;
+    let (rhs_header, rhs_info) = lookup_benchmark(rhs_name)?;
+    let comparison = crate::compare::compare_benchmarks(
+        &lhs_info,
+        lhs_run,
+        &rhs_info,
+        rhs_run,
+        options.baseline,
+        &options.metrics,
+        &options.names
+    )
+    .ok_or_else(|| {
+        Error::InvalidRunId(format!("Invalid run id specified, {}:{} or {}:{}", lhs_name, lhs_run, rhs_name, rhs_run))
+    })?;
+
+    let lhs = crate::report::ComparedBenchmark { name: lhs_name, header: &lhs_header, info: &lhs_info };
+    let rhs = crate::report::ComparedBenchmark { name: rhs_name, header: &rhs_header, info: &rhs_info };
+    present_comparison(&lhs, &rhs, comparison, options)
+}
+
+fn single_run(run_id: crate::types::RunId) -> Result<usize> {
+    match run_id {
+        crate::types::RunId::Index(run) => Ok(run),
+        _ => Err(Error::InvalidRunId("Comparisons must be between single runs, not ranges.".to_string()))
+    }
+}
+
+fn single_runs(lhs: crate::types::RunId, rhs: crate::types::RunId) -> Result<(usize, usize)> {
+    Ok((single_run(lhs)?, single_run(rhs)?))
+}
+
+// The latest runs not excluded as outliers, oldest first, for commands given no runs
+pub fn latest_included_runs(name: &str, count: usize) -> Result<Vec<usize>> {
+    let (_, info) = lookup_benchmark(name)?;
+    let mut runs: Vec<usize> = (0..info.benchmarks.len()).rev().filter(|run| !info.is_excluded(*run)).take(count).collect();
+    if runs.len() < count {
+        return Err(Error::InvalidRunId(format!("Only {} runs of {:?} aren't excluded as outliers.", runs.len(), name)));
+    }
+
+    runs.reverse();
+    Ok(runs)
+}
+
+// Excluded runs are still shown when asked for by id, but with a warning. It goes to
+// stderr so structured output stays parseable.
+fn warn_excluded(name: &str, info: &crate::types::IndividualBenchInfo, runs: &[usize]) {
+    use colored::*;
+    for run in runs.iter().filter(|run| info.is_excluded(**run)) {
+        eprintln!("{}", format!("Warning: run #{} of {:?} is excluded as an outlier.", run, name).yellow());
+    }
+}
+
+// Write a heatmap of a comparison, if one was asked for
+fn write_plot(
+    options: &crate::compare::CompareOptions,
+    title: &str,
+    columns: &[String],
+    matrix: &crate::matrix::Matrix,
+    noise_percent: f64
+) -> Result<()>
+{
+    match &options.plot {
+        Some(path) => fs::write(path, crate::plot::heatmap(title, columns, matrix, noise_percent))
+            .map_err(Error::io(format!("Failed to write the plot to {:?}", path))),
+        None => Ok(())
+    }
+}
+
+// Print a comparison in the chosen format, where lhs and rhs are the same benchmark unless comparing across two
+fn present_comparison(
+    lhs: &crate::report::ComparedBenchmark,
+    rhs: &crate::report::ComparedBenchmark,
+    comparison: crate::compare::Comparison,
+    options: &crate::compare::CompareOptions
+) -> Result<()>
+{
+    warn_excluded(lhs.name, lhs.info, &[comparison.lhs]);
+    warn_excluded(rhs.name, rhs.info, &[comparison.rhs]);
+    let noise_percent = lhs.header.noise_band();
+    let comparison = comparison.arranged(options, noise_percent);
+    let (title, columns) = if lhs.name == rhs.name {
+        (lhs.name.to_string(), vec![format!("#{}", comparison.lhs), format!("#{}", comparison.rhs)])
+    } else {
+        let columns = vec![format!("{} #{}", lhs.name, comparison.lhs), format!("{} #{}", rhs.name, comparison.rhs)];
+        (format!("{} → {}", lhs.name, rhs.name), columns)
+    };
+    write_plot(options, &title, &columns, &crate::matrix::Matrix::from(&comparison), noise_percent)?;
+
+    match crate::config::output_format() {
+        crate::config::OutputFormat::Markdown => return print_markdown_comparison(lhs, rhs, &comparison, &options.metrics),
+        format if format.is_structured() => return print_structured(&crate::report::compare_report(lhs, rhs, comparison)),
+        _ => {}
+    }
+
+    let describe = |side: &str, benchmark: &crate::report::ComparedBenchmark, run: usize| {
+        if lhs.name == rhs.name {
+            format!("{}, run #{}", side, run)
+        } else {
+            format!("{}, {} run #{}", side, benchmark.name, run)
+        }
+    };
+    let (baseline, other) = match comparison.baseline {
+        crate::compare::Baseline::Lhs => (describe("LHS", lhs, comparison.lhs), describe("RHS", rhs, comparison.rhs)),
+        crate::compare::Baseline::Rhs => (describe("RHS", rhs, comparison.rhs), describe("LHS", lhs, comparison.lhs))
+    };
+    println!("Baseline: {}, compared with {}. Changes within ±{}% are treated as noise.", baseline, other, noise_percent);
+
+    let mut output = prettytable::Table::new();
+    let several = options.metrics.len() > 1;
+    let value = value_title(&options.metrics);
+    let mut titles = vec!["Name", &format!("LHS {}", value), &format!("RHS {}", value), "Abs Diff", "% Diff", "X Speedup"]
+        .into_iter()
+        .map(prettytable::Cell::new)
+        .collect::<Vec<_>>();
+    if several {
+        titles.insert(1, prettytable::Cell::new("Metric"));
+    }
+    output.set_titles(prettytable::Row::new(titles));
+    for row in &comparison.rows {
+        // Red for regressions, green for improvements, on terminals that can show it
+        let style = match row.change(noise_percent) {
+            crate::compare::Change::Regression => "Fr",
+            crate::compare::Change::Improvement => "Fg",
+            crate::compare::Change::Unchanged => ""
+        };
+        let mut cells = vec![
+            prettytable::Cell::new(&row.name),
+            prettytable::Cell::new(&row.metric.format(row.lhs_value, &row.unit)),
+            prettytable::Cell::new(&row.metric.format(row.rhs_value, &row.unit)),
+            prettytable::Cell::new(&row.metric.format_diff(row.abs_diff, &row.unit)).style_spec(style),
+            prettytable::Cell::new(&format!("{:.3}", row.percent_diff)).style_spec(style),
+            prettytable::Cell::new(&format!("{:.3}", row.speedup)).style_spec(style),
+        ];
+        if several {
+            cells.insert(1, prettytable::Cell::new(row.metric.name()));
         }
+        output.add_row(prettytable::Row::new(cells));
+    }
+
+    println!("Performance Diffs: ");
+    output.printstd();
+    if !comparison.unmatched.is_empty() {
+        let both = if lhs.name == rhs.name { "runs" } else { "benchmarks" };
+        println!("Not in both {}, so not compared: {}", both, comparison.unmatched.join(", "));
+    }
+    print_complexity_changes(lhs, rhs, &comparison);
+    print_binary_comparison(&comparison.lhs_binary, &comparison.rhs_binary);
 
-        if run_id_1 >= num_runs {
-            println!("Invalid run id specified ({}), only {} runs recorded", run_id_1, num_runs);
-            return;
+    // Runs recorded outside of a git checkout have no revision to diff
+    if !lhs.shares_source(rhs) {
+        println!("Source Diffs: \n  Skipped, {} and {} are built from different source roots.", lhs.name, rhs.name);
+    } else if comparison.lhs_hash.is_empty() || comparison.rhs_hash.is_empty() {
+        println!("Source Diffs: \n  Unknown, at least one run has no recorded revision.");
+    } else {
+        // By now the table is printed, so a revision git can't find is reported rather than failing
+        match crate::git::diff(&lhs.header.source_root, &comparison.lhs_hash, &comparison.rhs_hash, true) {
+            Ok(diff) => println!("Source Diffs: \n{}", diff),
+            Err(e) => println!("Source Diffs: \n  Unavailable, {}", e)
         }
+    }
 
-        if run_id_2 >= num_runs {
-            println!("Invalid run id specified ({}), only {} runs recorded", run_id_2, num_runs);
-            return;
+    Ok(())
+}
+
+// Families of benchmarks which scale differently with N between the runs, such as BM_Sort/8,
+// BM_Sort/64, ... going from O(NlgN) to O(N^2).
+fn print_complexity_changes(
+    lhs: &crate::report::ComparedBenchmark,
+    rhs: &crate::report::ComparedBenchmark,
+    comparison: &crate::compare::Comparison
+)
+{
+    use colored::*;
+    let complexities = |benchmark: &crate::report::ComparedBenchmark, run: usize| {
+        crate::complexity::complexities(&benchmark.info.benchmarks[run], &crate::metric::Metric::RealTime)
+    };
+    for change in crate::complexity::changes(&complexities(lhs, comparison.lhs), &complexities(rhs, comparison.rhs)) {
+        let line = format!("Complexity of {} changed from {} to {}", change.name, change.from.name(), change.to.name());
+        if change.to > change.from {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line.green());
         }
+    }
+}
 
-        let bench_results_1 = &info.benchmarks[run_id_1];
-        let bench_results_2 = &info.benchmarks[run_id_2];
-        let mut output = prettytable::Table::new();
-        output.set_titles(row!["Name", "LHS Time", "RHS Time", "Abs Diff", "% Diff", "X Speedup"]);
+// Compare more than two runs of a benchmark, each relative to the run in the baseline column
+pub fn print_matrix_comparison(
+    name: &str,
+    run_ids: Vec<crate::types::RunId>,
+    baseline: usize,
+    options: &crate::compare::CompareOptions
+) -> Result<()>
+{
+    let runs = run_ids.into_iter().map(single_run).collect::<Result<Vec<usize>>>()?;
+    let (header, info) = lookup_benchmark(name)?;
+    warn_excluded(name, &info, &runs);
+    let noise_percent = header.noise_band();
+    let matrix = crate::matrix::compare_matrix(&info, &runs, baseline, &options.metrics)
+        .ok_or_else(|| Error::InvalidRunId(format!("Invalid run id specified, only {} runs recorded", info.benchmarks.len())))?
+        .arranged(options, noise_percent);
+    let columns: Vec<String> = runs.iter().map(|run| format!("#{}", run)).collect();
+    write_plot(options, name, &columns, &matrix, noise_percent)?;
 
-        for result in bench_results_1.iter().zip(bench_results_2.iter()) {
-            let (lhs, rhs) = &result;
+    match crate::config::output_format() {
+        crate::config::OutputFormat::Markdown => return print_markdown_matrix(name, &header, &matrix, &columns, &options.metrics),
+        format if format.is_structured() => return print_structured(&crate::report::matrix_report(name, &header, &info, matrix)),
+        _ => {}
+    }
+
+    println!("Baseline: run #{}. Changes within ±{}% are treated as noise.", runs[baseline], noise_percent);
+    let several = options.metrics.len() > 1;
+    let mut titles: Vec<prettytable::Cell> = std::iter::once("Name".to_string())
+        .chain(column_titles(columns.as_slice(), baseline))
+        .map(|title| prettytable::Cell::new(&title))
+        .collect();
+    if several {
+        titles.insert(1, prettytable::Cell::new("Metric"));
+    }
 
-            let name = &lhs.name;
-            let lhs_time = lhs.real_time;
-            let rhs_time = rhs.real_time;
-            let abs_diff = rhs_time - lhs_time;
-            let percent_diff = 100.0 * (rhs_time - lhs_time) / rhs_time;
-            let improvement = lhs_time / rhs_time;
-            output.add_row(row![
-                name.to_string(),
-                format!("{:.3}", lhs_time),
-                format!("{:.3}", rhs_time),
-                format!("{:.3}", abs_diff),
-                format!("{:.3}", percent_diff),
-                format!("{:.3}", improvement)
-            ]);
+    let mut output = prettytable::Table::new();
+    output.set_titles(prettytable::Row::new(titles));
+    for row in &matrix.rows {
+        let mut cells = vec![prettytable::Cell::new(&row.name)];
+        if several {
+            cells.push(prettytable::Cell::new(row.metric.name()));
         }
+        for column in 0..row.values.len() {
+            let style = match row.change(column, noise_percent) {
+                crate::compare::Change::Regression => "Fr",
+                crate::compare::Change::Improvement => "Fg",
+                crate::compare::Change::Unchanged => ""
+            };
+            let text = match (row.values[column], row.percent_diffs[column]) {
+                (Some(value), Some(percent)) => format!("{} ({:+.1}%)", row.metric.format(value, ""), percent),
+                (Some(value), None) => row.metric.format(value, ""),
+                (None, _) => "-".to_string()
+            };
+            cells.push(prettytable::Cell::new(&text).style_spec(style));
+        }
+        output.add_row(prettytable::Row::new(cells));
+    }
+
+    println!("Performance Diffs: ");
+    output.printstd();
+    Ok(())
+}
+
+fn column_titles(columns: &[String], baseline: usize) -> Vec<String> {
+    let title = |(i, column): (usize, &String)| if i == baseline { format!("{} (baseline)", column) } else { column.clone() };
+    columns.iter().enumerate().map(title).collect()
+}
+
+// As print_matrix_comparison, but as markdown ready to paste into a pull request
+fn print_markdown_matrix(
+    name: &str,
+    header: &crate::types::BenchHeader,
+    matrix: &crate::matrix::Matrix,
+    columns: &[String],
+    metrics: &[crate::metric::Metric]
+) -> Result<()>
+{
+    println!("### Benchmark comparison: {}\n", name);
+    println!("Runs {}, percentages are relative to run {}.\n", columns.join(", "), columns[matrix.baseline]);
+
+    let several = metrics.len() > 1;
+    let titles = column_titles(columns, matrix.baseline);
+    let mut table = format!("| Benchmark |{} {} |\n|:--|", if several { " Metric |" } else { "" }, titles.join(" | "));
+    table.push_str(if several { ":--|" } else { "" });
+    table.push_str(&"--:|".repeat(columns.len()));
+    table.push('\n');
 
-        let lhs_hash = &info.source_hashes[run_id_1];
-        let rhs_hash = &info.source_hashes[run_id_2];
+    let (mut regressions, mut improvements) = (0, 0);
+    for row in &matrix.rows {
+        table.push_str(&format!("| `{}` |", row.name.replace('|', "\\|")));
+        if several {
+            table.push_str(&format!(" {} |", row.metric.name()));
+        }
+        for column in 0..row.values.len() {
+            let indicator = match row.change(column, header.noise_band()) {
+                crate::compare::Change::Regression => {
+                    regressions += 1;
+                    " 🔴"
+                }
+                crate::compare::Change::Improvement => {
+                    improvements += 1;
+                    " 🟢"
+                }
+                crate::compare::Change::Unchanged => ""
+            };
+            let cell = match (row.values[column].map(|value| row.metric.format(value, &row.unit)), row.percent_diffs[column]) {
+                (Some(value), Some(percent)) => format!("{} ({:+.2}%{})", value, percent, indicator),
+                (Some(value), None) => value,
+                (None, _) => "–".to_string()
+            };
+            table.push_str(&format!(" {} |", cell));
+        }
+        table.push('\n');
+    }
 
-        println!("Performance Diffs: \n{}", output);
-        println!("Source Diffs: \n{}", crate::git::diff(&header.source_root, &lhs_hash, &rhs_hash));
+    let plural = |count: usize, word: &str| format!("{} {}{}", count, word, if count == 1 { "" } else { "s" });
+    println!(
+        "**{}, {}** against the baseline (changes within ±{}% are ignored)\n",
+        plural(regressions, "regression"),
+        plural(improvements, "improvement"),
+        header.noise_band()
+    );
+    println!("{}", table);
+    Ok(())
+}
+
+// Columns of times are titled as such, anything else is just a value
+fn value_title(metrics: &[crate::metric::Metric]) -> &'static str {
+    if metrics.iter().all(crate::metric::Metric::is_time) {
+        "Time"
+    } else {
+        "Value"
     }
 }
 
-pub fn print_current_benchmarks() {
-    let benches = crate::config::read_top_level_config();
+// As print_comparison, but as markdown ready to paste into a pull request
+fn print_markdown_comparison(
+    lhs: &crate::report::ComparedBenchmark,
+    rhs: &crate::report::ComparedBenchmark,
+    comparison: &crate::compare::Comparison,
+    metrics: &[crate::metric::Metric]
+) -> Result<()>
+{
+    let header = lhs.header;
+    let baseline = match comparison.baseline {
+        crate::compare::Baseline::Lhs => "the first run",
+        crate::compare::Baseline::Rhs => "the second run"
+    };
+    if lhs.name == rhs.name {
+        println!("### Benchmark comparison: {}\n", lhs.name);
+        println!(
+            "Run #{} (`{}`) → run #{} (`{}`), percentages are relative to {}.\n",
+            comparison.lhs,
+            short_hash(&comparison.lhs_hash),
+            comparison.rhs,
+            short_hash(&comparison.rhs_hash),
+            baseline
+        );
+    } else {
+        println!("### Benchmark comparison: {} → {}\n", lhs.name, rhs.name);
+        println!(
+            "{} run #{} (`{}`) → {} run #{} (`{}`), percentages are relative to {}.\n",
+            lhs.name,
+            comparison.lhs,
+            short_hash(&comparison.lhs_hash),
+            rhs.name,
+            comparison.rhs,
+            short_hash(&comparison.rhs_hash),
+            baseline
+        );
+    }
+
+    let (mut regressions, mut improvements, mut unchanged) = (0, 0, 0);
+    let several = metrics.len() > 1;
+    let value = value_title(metrics);
+    let mut table = if several {
+        format!("| Benchmark | Metric | LHS {v} | RHS {v} | Abs Diff | % Diff | X Speedup | |\n|:--|:--|", v = value)
+    } else {
+        format!("| Benchmark | LHS {v} | RHS {v} | Abs Diff | % Diff | X Speedup | |\n|:--|", v = value)
+    };
+    table.push_str("--:|--:|--:|--:|--:|:-:|\n");
+    for row in &comparison.rows {
+        // The arrow is which way the value went, up being bad for times and good for throughput
+        let arrow = if row.abs_diff > 0.0 { "▲" } else { "▼" };
+        let indicator = match row.change(header.noise_band()) {
+            crate::compare::Change::Regression => {
+                regressions += 1;
+                format!("🔴 {}", arrow)
+            }
+            crate::compare::Change::Improvement => {
+                improvements += 1;
+                format!("🟢 {}", arrow)
+            }
+            crate::compare::Change::Unchanged => {
+                unchanged += 1;
+                "⚪".to_string()
+            }
+        };
+
+        let metric = if several { format!(" {} |", row.metric.name()) } else { "".to_string() };
+        table.push_str(&format!(
+            "| `{}` |{} {} | {} | {} | {:+.2}% | {:.3}x | {} |\n",
+            row.name.replace('|', "\\|"),
+            metric,
+            row.metric.format(row.lhs_value, &row.unit),
+            row.metric.format(row.rhs_value, &row.unit),
+            row.metric.format_diff(row.abs_diff, &row.unit),
+            row.percent_diff,
+            row.speedup,
+            indicator
+        ));
+    }
+
+    let plural = |count: usize, word: &str| format!("{} {}{}", count, word, if count == 1 { "" } else { "s" });
+    println!(
+        "**{}, {}, {} unchanged** (within ±{}%)\n",
+        plural(regressions, "regression"),
+        plural(improvements, "improvement"),
+        unchanged,
+        header.noise_band()
+    );
+    println!("{}", table);
+    if !comparison.unmatched.is_empty() {
+        let unmatched: Vec<String> = comparison.unmatched.iter().map(|name| format!("`{}`", name)).collect();
+        let both = if lhs.name == rhs.name { "runs" } else { "benchmarks" };
+        println!("Not in both {}, so not compared: {}.\n", both, unmatched.join(", "));
+    }
+
+    if let (Some(lhs), Some(rhs)) = (&comparison.lhs_binary, &comparison.rhs_binary) {
+        let same = if lhs.sha256 == rhs.sha256 { "an identical binary" } else { "different binaries" };
+        println!("Both runs used {}.\n", same);
+    }
+
+    if !lhs.shares_source(rhs) {
+        println!("No source diff, the benchmarks are built from different source roots.");
+    } else if !comparison.lhs_hash.is_empty() && !comparison.rhs_hash.is_empty() {
+        println!("<details>");
+        println!("<summary>Source diff ({}..{})</summary>\n", short_hash(&comparison.lhs_hash), short_hash(&comparison.rhs_hash));
+        match crate::git::diff(&header.source_root, &comparison.lhs_hash, &comparison.rhs_hash, false) {
+            Ok(diff) if diff.trim().is_empty() => println!("No source changes.\n"),
+            Ok(diff) => println!("```diff\n{}\n```\n", diff.trim_end()),
+            Err(e) => println!("Unavailable, {}.\n", e)
+        }
+        println!("</details>");
+    }
+
+    Ok(())
+}
+
+fn describe_binary(binary: &crate::types::BinaryInfo) -> String {
+    format!(
+        "sha256:{} size:{} mtime:{} build-id:{} compiler:{} flags:{:?}",
+        binary.sha256.get(..12).unwrap_or(&binary.sha256),
+        binary.size,
+        binary.mtime,
+        binary.build_id.as_ref().map_or("unknown", |id| id.as_str()),
+        binary.compiler.as_ref().map_or("unknown", |c| c.as_str()),
+        binary.flags.as_ref().map_or("unknown", |f| f.as_str())
+    )
+}
+
+fn print_binary_comparison(lhs: &Option<crate::types::BinaryInfo>, rhs: &Option<crate::types::BinaryInfo>) {
+    use colored::*;
+
+    println!("Binaries: ");
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => {
+            println!("  LHS: {}", describe_binary(lhs));
+            println!("  RHS: {}", describe_binary(rhs));
+
+            let same_build = lhs.compiler == rhs.compiler && lhs.flags == rhs.flags;
+            match (lhs.sha256 == rhs.sha256, same_build) {
+                (true, true) => println!("  {}", "Both runs used an identical binary.".green()),
+                (true, false) => println!("  {}", "Identical binary, but the recorded compiler or flags differ.".red()),
+                (false, true) => println!("  {}", "Binaries differ.".yellow()),
+                (false, false) => println!("  {}", "Binaries differ, and were built with different compilers or flags.".red())
+            }
+        }
+        (_, _) => println!("  Unknown, at least one run was recorded before binaries were fingerprinted.")
+    }
+}
+
+// Candidates for the shell completion scripts, one per line. Anything going
+// wrong just means no candidates, rather than noise in the user's shell.
+pub fn print_completion_candidates(kind: &str, name: Option<&str>) {
+    let workspace = match crate::config::workspace() {
+        Ok(workspace) => workspace,
+        Err(_) => return
+    };
+
+    let benches = workspace.headers().unwrap_or_default();
+    match (kind, name) {
+        ("names", _) => {
+            let mut names: Vec<&crate::types::BenchId> = benches.keys().collect();
+            names.sort();
+            for name in names {
+                println!("{}", name);
+            }
+        }
+        ("runs", Some(name)) if benches.contains_key(name) => {
+            let info = match workspace.runs(name) {
+                Ok(info) => info,
+                Err(_) => return
+            };
+
+            for i in 0..info.benchmarks.len() {
+                println!("{}", i);
+            }
+
+            println!("latest\nprevious");
+            let mut tags: Vec<String> = info.tags.iter().flatten().map(|(k, v)| format!("tag:{}={}", k, v)).collect();
+            tags.sort();
+            tags.dedup();
+            for tag in tags {
+                println!("{}", tag);
+            }
+        }
+        (_, _) => {}
+    }
+}
+
+pub fn print_current_benchmarks() -> Result<()> {
+    let workspace = crate::config::workspace()?;
+    let benches = workspace.headers()?;
+    if crate::config::output_format().is_structured() {
+        return print_structured(&crate::report::list_report(&workspace.root().to_string_lossy(), &benches));
+    }
+
+    println!("Workspace: {:?}", workspace.root());
     for (id, info) in benches {
         println!(
             "> Name: {:?}\n  Description: {:?}\n  Source Location: {:?}\
@@ -163,126 +779,824 @@ pub fn print_current_benchmarks() {
             id, info.description, info.source_root, info.source_bin
         );
     }
+
+    Ok(())
 }
 
-pub fn print_individual_bench_info(name: &str) {
-    if let Some((header, info)) = lookup_benchmark(name) {
-        println!("> Name: {}", name);
-        println!("  Description: {}", header.description);
-        println!("  Source Location: {}", header.source_root);
-        println!("  Executable Location: {}", header.source_bin);
-        println!("  Previous run information: ");
-        for i in 0..info.commentary.len() {
-            println!("  :: Run #{} (git:{}): {}", i, info.source_hashes[i].get(..8).unwrap(), info.commentary[i]);
-        }
+pub fn print_individual_bench_info(name: &str, filter: &crate::types::RunTags, runs: &crate::types::RunId) -> Result<()> {
+    let (header, info) = lookup_benchmark(name)?;
+    let (start, end) = run_bounds(runs, info.commentary.len())?;
+    let selected: Vec<usize> = (start..end).filter(|i| tags_match(&info.tags[*i], filter)).collect();
+    if crate::config::output_format().is_structured() {
+        return print_structured(&crate::report::info_report(name, &header, &info, &selected));
+    }
+
+    println!("> Name: {}", name);
+    println!("  Description: {}", header.description);
+    println!("  Source Location: {}", header.source_root);
+    println!("  Executable Location: {}", header.source_bin);
+    println!("  Previous run information: ");
+    let noise = crate::analysis::run_noise(&info);
+    let threshold = crate::analysis::OUTLIER_FACTOR * header.noise_band();
+    for i in selected {
+        let date = if info.dates[i].is_empty() { "".to_string() } else { format!(" @{}", info.dates[i]) };
+        let tags = if info.tags[i].is_empty() { "".to_string() } else { format!(" [{}]", format_tags(&info.tags[i])) };
+        println!("  :: Run #{} (git:{}){}{}: {}", i, short_hash(&info.source_hashes[i]), date, tags, info.commentary[i]);
+        print_run_noise(&noise[i], threshold);
     }
+
+    Ok(())
 }
 
-#[allow(dead_code)]
-pub fn print_comparison_with_prompt() {
-    unimplemented!();
+// A run's noise under its summary, in yellow if it looks like an outlier
+fn print_run_noise(noise: &crate::analysis::RunNoise, threshold_percent: f64) {
+    use colored::*;
+    let cv = noise.cv_percent.map(|cv| format!("cv {:.1}%", cv));
+    let deviation = noise.deviation_percent.map(|deviation| format!("{:+.1}% from the rolling median", deviation));
+    let parts: Vec<String> = cv.into_iter().chain(deviation).collect();
+    if parts.is_empty() {
+        return;
+    }
+
+    let line = format!("       noise: {}", parts.join(", "));
+    if noise.is_suspicious(threshold_percent) {
+        println!("{}", line.yellow());
+    } else {
+        println!("{}", line);
+    }
 }
 
-pub fn run_individual_benchmark(name: &str) {
-    let desc: String = dialoguer::Input::new().with_prompt("What has changed since the last run?").interact().unwrap();
+// Print the runs of a benchmark noisier than the threshold, by default a multiple of its
+// noise band, and optionally tag them as excluded so analyses leave them out.
+pub fn print_outliers(name: &str, threshold: Option<f64>, exclude: bool) -> Result<()> {
+    let _lock = if exclude { Some(crate::config::lock_workspace()?) } else { None };
+    let (header, mut info) = lookup_benchmark(name)?;
+    let threshold = threshold.unwrap_or(crate::analysis::OUTLIER_FACTOR * header.noise_band());
+    let noise = crate::analysis::run_noise(&info);
+    let outliers: Vec<usize> = (0..noise.len()).filter(|run| noise[*run].is_suspicious(threshold)).collect();
 
-    if let Some((header, mut info)) = lookup_benchmark(name) {
-        let exe = &header.source_bin;
-        if !path::Path::new(&header.source_bin).exists() {
-            println!("Source bin {} no longer exists!", header.source_bin);
-            std::process::exit(1);
+    if exclude && !outliers.is_empty() {
+        for run in &outliers {
+            info.tags[*run].insert(crate::types::EXCLUDED_TAG.to_string(), "true".to_string());
         }
+        crate::config::workspace()?.write_runs(name, &info)?;
+    }
+
+    if crate::config::output_format().is_structured() {
+        return print_structured(&crate::report::outliers_report(name, &info, &noise, &outliers, threshold, exclude));
+    }
 
-        let output = process::Command::new(&exe).arg("--benchmark_format=json").output().unwrap();
+    println!("> {}: {} of {} runs noisier than ±{}%", name, outliers.len(), info.benchmarks.len(), threshold);
+    for i in &outliers {
+        let date = if info.dates[*i].is_empty() { "".to_string() } else { format!(" @{}", info.dates[*i]) };
+        let excluded = if info.is_excluded(*i) { " [excluded]" } else { "" };
+        println!("  :: Run #{} (git:{}){}{}: {}", i, short_hash(&info.source_hashes[*i]), date, excluded, info.commentary[*i]);
+        print_run_noise(&noise[*i], threshold);
+    }
 
-        let raw: String = String::from_utf8_lossy(&output.stdout).to_string();
-        let new_benches: crate::types::BenchRunResult = serde_json::from_str(&raw).unwrap();
-        info.benchmarks.push(new_benches.benchmarks);
-        info.commentary.push(desc);
-        info.source_hashes.push(crate::git::hash(&header.source_root));
-        let path = crate::config::get_individual_config_file(name);
-        fs::write(&path, serde_json::to_string_pretty(&info).unwrap()).unwrap();
+    if exclude && !outliers.is_empty() {
+        println!("Tagged as {0}, undo with: benchviz tag {1} <run> -r {0}", crate::types::EXCLUDED_TAG, name);
     }
+
+    Ok(())
 }
 
-pub fn create_new_individual_benchmark() {
-    let name: String = dialoguer::Input::new().with_prompt("Enter a name for the benchmark").interact().unwrap();
-    let src: String = dialoguer::Input::new().with_prompt("Enter a source directory location").interact().unwrap();
-    let bin: String = dialoguer::Input::new().with_prompt("Enter an executable path").interact().unwrap();
-    let desc: String = dialoguer::Input::new().with_prompt("Describe this benchmark").interact().unwrap();
+// Print the runs where a benchmark's performance stepped, with the changes between
+// the run before and the run after as the likely cause.
+pub fn print_change_points(name: &str, metrics: &[crate::metric::Metric], filter: Option<&regex::Regex>) -> Result<()> {
+    use colored::*;
+    let (header, info) = lookup_benchmark(name)?;
+    let mut analyses = crate::analysis::analyze(&info, metrics, header.noise_band());
+    if let Some(filter) = filter {
+        analyses.retain(|analysis| filter.is_match(&analysis.name));
+    }
 
-    let mut benches = crate::config::read_top_level_config();
-    match benches.get(&name) {
-        Some(_) => {
-            println!("Name {:?} already exists in benchmarks.", name);
+    if crate::config::output_format().is_structured() {
+        return print_structured(&crate::report::analysis_report(name, &header, &info, analyses));
+    }
+
+    let mut steps: Vec<(&crate::analysis::SeriesAnalysis, &crate::analysis::ChangePoint)> = analyses
+        .iter()
+        .flat_map(|analysis| analysis.change_points.iter().map(move |point| (analysis, point)))
+        .collect();
+    steps.sort_by_key(|(_, point)| point.run);
+    println!(
+        "> {}: {} step{} found in {} runs, changes within ±{}% are ignored",
+        name,
+        steps.len(),
+        if steps.len() == 1 { "" } else { "s" },
+        info.benchmarks.len(),
+        header.noise_band()
+    );
+
+    let mut current = None;
+    for (analysis, point) in steps {
+        if current != Some(point.run) {
+            current = Some(point.run);
+            let date = if info.dates[point.run].is_empty() { "".to_string() } else { format!(" @{}", info.dates[point.run]) };
+            let (from, to) = (&info.source_hashes[point.previous_run], &info.source_hashes[point.run]);
+            let range = if from.is_empty() || to.is_empty() {
+                "unknown".to_string()
+            } else {
+                format!("{}..{}", short_hash(from), short_hash(to))
+            };
+            println!("  :: Run #{} (git:{}){}: {}", point.run, range, date, info.commentary[point.run]);
         }
-        None => {
-            // Author skeleton info.json file
-            let individual = crate::config::get_individual_config_file(&name);
-            fs::create_dir(individual.parent().unwrap()).unwrap();
-            fs::File::create(&individual).unwrap();
-            let blank_individual_config = crate::types::IndividualBenchInfo {
-                context:       None,
-                benchmarks:    vec![],
-                commentary:    vec![],
-                source_hashes: vec![]
+
+        let metric = if metrics.len() > 1 { format!(" ({})", analysis.metric.name()) } else { "".to_string() };
+        let (before, after) = (analysis.metric.format(point.before, ""), analysis.metric.format(point.after, &analysis.unit));
+        let step = format!("{}{}: {} → {} ({:+.1}%)", analysis.name, metric, before, after, point.percent_diff);
+        match point.change {
+            crate::compare::Change::Regression => println!("       {}", step.red()),
+            _ => println!("       {}", step.green())
+        }
+    }
+
+    Ok(())
+}
+
+// Print the complexity of each family of benchmarks in a run, such as BM_Sort/8, BM_Sort/64, ...,
+// or given two runs, which families changed class between them.
+pub fn print_complexity(
+    name: &str,
+    run_ids: Vec<crate::types::RunId>,
+    metric: &crate::metric::Metric,
+    selection: &crate::cli::Selection,
+    plot: Option<&str>
+) -> Result<()>
+{
+    let runs = run_ids.into_iter().map(single_run).collect::<Result<Vec<usize>>>()?;
+    let (_, info) = lookup_benchmark(name)?;
+    let mut families = Vec::new();
+    for run in &runs {
+        let results = info.benchmarks.get(*run).ok_or_else(|| {
+            Error::InvalidRunId(format!("Invalid run id specified, only {} runs recorded", info.benchmarks.len()))
+        })?;
+        // Conditions pick out the results fitted, such as threads=8, and filters the families
+        let results: Vec<crate::types::BenchResult> = results
+            .iter()
+            .filter(|result| selection.conditions.as_ref().is_none_or(|conditions| conditions.is_match(&result.name)))
+            .cloned()
+            .collect();
+        let mut found = crate::complexity::complexities(&results, metric);
+        if let Some(filter) = &selection.filter {
+            found.retain(|family| filter.is_match(&family.name));
+        }
+        families.push(found);
+    }
+    warn_excluded(name, &info, &runs);
+    let changes = if families.len() == 2 { crate::complexity::changes(&families[0], &families[1]) } else { Vec::new() };
+
+    let columns: Vec<String> = runs.iter().map(|run| format!("#{}", run)).collect();
+    if let Some(path) = plot {
+        let unit = families.iter().flatten().next().map(|family| family.unit.clone()).unwrap_or_default();
+        let y_label = format!("{} ({})", metric.name(), unit);
+        fs::write(path, crate::plot::complexity(name, &columns, &families, &y_label))
+            .map_err(Error::io(format!("Failed to write the plot to {:?}", path)))?;
+    }
+
+    if crate::config::output_format().is_structured() {
+        return print_structured(&crate::report::complexity_report(name, &info, metric, &runs, families, changes));
+    }
+
+    let mut names: Vec<&str> = Vec::new();
+    for family in families.iter().flatten() {
+        if !names.contains(&family.name.as_str()) {
+            names.push(&family.name);
+        }
+    }
+    let plural = if names.len() == 1 { "family" } else { "families" };
+    println!("> {}: complexity of {} {} in {} time", name, names.len(), plural, metric.name());
+
+    let mut output = prettytable::Table::new();
+    let titles = std::iter::once("Family").chain(std::iter::once("Sizes")).chain(columns.iter().map(String::as_str));
+    output.set_titles(prettytable::Row::new(titles.map(prettytable::Cell::new).collect()));
+    for family_name in names {
+        let found: Vec<Option<&crate::complexity::FamilyComplexity>> =
+            families.iter().map(|families| families.iter().find(|family| family.name == family_name)).collect();
+        let points = &found.iter().flatten().next().map(|family| family.points.clone()).unwrap_or_default();
+        let sizes = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => format!("{}..{} ({})", first.0, last.0, points.len()),
+            _ => "-".to_string()
+        };
+        // Red when the class grew, green when it shrank
+        let style = match changes.iter().find(|change| change.name == family_name) {
+            Some(change) if change.to > change.from => "Fr",
+            Some(_) => "Fg",
+            None => ""
+        };
+
+        let mut cells = vec![prettytable::Cell::new(family_name), prettytable::Cell::new(&sizes)];
+        for family in found {
+            // Every run gets a cell, so each stays under its own heading
+            let fit = family.and_then(|family| match (&family.reported, &family.fitted) {
+                (Some(fit), _) => Some((fit, "reported")),
+                (None, Some(fit)) => Some((fit, "fitted")),
+                (None, None) => None
+            });
+            let text = match fit {
+                Some((fit, source)) if fit.rms_percent.is_nan() => format!("{} ({})", fit.complexity.name(), source),
+                Some((fit, source)) => format!("{} ({}, rms {:.1}%)", fit.complexity.name(), source, fit.rms_percent),
+                None => "-".to_string()
             };
-            fs::write(&individual, serde_json::to_string_pretty(&blank_individual_config).unwrap()).unwrap();
+            cells.push(prettytable::Cell::new(&text).style_spec(style));
+        }
+        output.add_row(prettytable::Row::new(cells));
+    }
+    output.printstd();
+
+    if runs.len() == 2 {
+        println!("{} of them changed class between runs #{} and #{}.", changes.len(), runs[0], runs[1]);
+    }
+
+    Ok(())
+}
+
+// Print how each multithreaded family of benchmarks in a run speeds up with more threads, or
+// given two runs, how that changed between them.
+pub fn print_scaling(
+    name: &str,
+    run_ids: Vec<crate::types::RunId>,
+    metric: &crate::metric::Metric,
+    selection: &crate::cli::Selection,
+    plot: Option<&str>
+) -> Result<()>
+{
+    let runs = run_ids.into_iter().map(single_run).collect::<Result<Vec<usize>>>()?;
+    let (header, info) = lookup_benchmark(name)?;
+    warn_excluded(name, &info, &runs);
+    let mut families = Vec::new();
+    for run in &runs {
+        let results: Vec<crate::types::BenchResult> =
+            info.benchmarks[*run].iter().filter(|result| selection.is_match(&result.name)).cloned().collect();
+        families.push(crate::scaling::scaling(&results, metric));
+    }
+
+    let columns: Vec<String> = runs.iter().map(|run| format!("#{}", run)).collect();
+    if let Some(path) = plot {
+        fs::write(path, crate::plot::scaling(name, &columns, &families))
+            .map_err(Error::io(format!("Failed to write the plot to {:?}", path)))?;
+    }
 
-            // Update top level json file
-            let top_level = crate::config::get_top_level_config_file();
+    if crate::config::output_format().is_structured() {
+        return print_structured(&crate::report::scaling_report(name, &info, metric, &runs, families));
+    }
+
+    let mut names: Vec<&str> = Vec::new();
+    let mut counts: Vec<i64> = Vec::new();
+    for family in families.iter().flatten() {
+        if !names.contains(&family.name.as_str()) {
+            names.push(&family.name);
+        }
+        counts.extend(family.points.iter().map(|point| point.threads));
+    }
+    counts.sort_unstable();
+    counts.dedup();
+    println!("> {}: speedup in {} by threads, relative to the fewest each ran on, with parallel efficiency", name, metric.name());
+    if runs.len() == 2 {
+        println!("Changes within ±{}% between runs #{} and #{} are treated as noise.", header.noise_band(), runs[0], runs[1]);
+    }
 
-            benches.insert(
-                name.to_string(),
-                crate::types::BenchHeader {
-                    source_root: src.to_string(),
-                    source_bin:  bin.to_string(),
-                    description: desc.to_string()
+    let mut output = prettytable::Table::new();
+    let titles = std::iter::once("Family".to_string()).chain(counts.iter().map(|threads| format!("{} threads", threads)));
+    output.set_titles(prettytable::Row::new(titles.map(|title| prettytable::Cell::new(&title)).collect()));
+    for family_name in names {
+        let found: Vec<Option<&crate::scaling::FamilyScaling>> =
+            families.iter().map(|families| families.iter().find(|family| family.name == family_name)).collect();
+        let mut cells = vec![prettytable::Cell::new(family_name)];
+        for threads in &counts {
+            let points: Vec<Option<&crate::scaling::ScalingPoint>> =
+                found.iter().map(|family| family.and_then(|family| family.at(*threads))).collect();
+            let cell = match points.as_slice() {
+                [Some(point)] => prettytable::Cell::new(&format!("{:.2}x ({:.0}%)", point.speedup, point.efficiency)),
+                [Some(before), Some(after)] => {
+                    // Red when scaling got worse, green when it got better
+                    let percent = 100.0 * (after.speedup - before.speedup) / before.speedup;
+                    let style = match percent {
+                        percent if percent.abs() < header.noise_band() => "",
+                        percent if percent < 0.0 => "Fr",
+                        _ => "Fg"
+                    };
+                    let text = format!(
+                        "{:.2}x → {:.2}x ({:.0}% → {:.0}%)",
+                        before.speedup, after.speedup, before.efficiency, after.efficiency
+                    );
+                    prettytable::Cell::new(&text).style_spec(style)
                 }
-            );
-            fs::write(&top_level, serde_json::to_string_pretty(&benches).unwrap()).unwrap();
+                _ => prettytable::Cell::new("-")
+            };
+            cells.push(cell);
         }
+        output.add_row(prettytable::Row::new(cells));
     }
+    output.printstd();
+    Ok(())
 }
 
-pub fn remove_benchmark_run(name: &str, run_id: &crate::types::RunId) {
-    let mut info = crate::config::read_individual_config(&name);
-    match run_id {
-        crate::types::RunId::All => {
-            info.benchmarks.clear();
-            info.commentary.clear();
-            info.source_hashes.clear();
+// Print a run tabulated with one part of its benchmarks' names across, such as time against
+// size with a row for each thread count.
+pub fn print_pivot(
+    name: &str,
+    run_id: crate::types::RunId,
+    key: &str,
+    metric: &crate::metric::Metric,
+    selection: &crate::cli::Selection,
+    plot: Option<&str>
+) -> Result<()>
+{
+    let run = single_run(run_id)?;
+    let (_, info) = lookup_benchmark(name)?;
+    let results: Vec<crate::types::BenchResult> =
+        info.benchmarks[run].iter().filter(|result| selection.is_match(&result.name)).cloned().collect();
+    let table = crate::name::pivot(&results, key, metric)
+        .ok_or_else(|| Error::Invalid(format!("No benchmarks in run #{} have {}", run, metric.name())))?;
+    if table.rows.is_empty() {
+        return Err(Error::Invalid(format!("No benchmarks in run #{} have {:?} in their names", run, key)));
+    }
+
+    // Throughput's unit is its name
+    let value_label = match table.unit.as_str() {
+        unit if unit.is_empty() || unit == metric.name() => metric.name().to_string(),
+        unit => format!("{} ({})", metric.name(), unit)
+    };
+    if let Some(path) = plot {
+        let series: Vec<crate::plot::Series> = table
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(color, row)| crate::plot::Series { label: row.name.clone(), color, points: table.points(row), dashed: false })
+            .collect();
+        fs::write(path, crate::plot::log_log(&format!("{} #{}", name, run), key, &value_label, &series))
+            .map_err(Error::io(format!("Failed to write the plot to {:?}", path)))?;
+    }
+
+    let cell = |value: &Option<f64>| value.map_or("-".to_string(), |value| metric.format(value, ""));
+    match crate::config::output_format() {
+        crate::config::OutputFormat::Markdown => {
+            println!("### {} run #{}: {} by {}\n", name, run, value_label, key);
+            println!("| Benchmark | {} |", table.columns.join(" | "));
+            println!("|:--|{}", "--:|".repeat(table.columns.len()));
+            for row in &table.rows {
+                let values: Vec<String> = row.values.iter().map(cell).collect();
+                println!("| `{}` | {} |", row.name.replace('|', "\\|"), values.join(" | "));
+            }
+            return Ok(());
         }
-        crate::types::RunId::Index(val) => {
-            info.benchmarks.remove(*val);
-            info.commentary.remove(*val);
-            info.source_hashes.remove(*val);
+        format if format.is_structured() => return print_structured(&crate::report::pivot_report(name, &info, run, table)),
+        _ => {}
+    }
+
+    println!("> {}, run #{}: {} by {}", name, run, value_label, key);
+    let mut output = prettytable::Table::new();
+    let titles = std::iter::once("Name").chain(table.columns.iter().map(String::as_str));
+    output.set_titles(prettytable::Row::new(titles.map(prettytable::Cell::new).collect()));
+    for row in &table.rows {
+        let cells = std::iter::once(row.name.clone()).chain(row.values.iter().map(cell));
+        output.add_row(prettytable::Row::new(cells.map(|text| prettytable::Cell::new(&text)).collect()));
+    }
+    output.printstd();
+    Ok(())
+}
+
+fn describe_run(info: &crate::types::IndividualBenchInfo, index: usize) -> String {
+    let date = if info.dates[index].is_empty() { "unknown date" } else { &info.dates[index] };
+    format!("#{} {} (git:{}) {}", index, date, short_hash(&info.source_hashes[index]), info.commentary[index])
+}
+
+pub fn print_comparison_with_prompt(options: &crate::compare::CompareOptions) -> Result<()> {
+    let name = prompt_benchmark_name()?;
+    let info = crate::config::workspace()?.runs(&name)?;
+    let num_runs = info.benchmarks.len();
+    if num_runs < 2 {
+        return Err(Error::InvalidRunId(format!(
+            "{} has {} runs recorded, at least two are needed for a comparison.",
+            name, num_runs
+        )));
+    }
+
+    // Most recent first, since that's nearly always what's being compared
+    let runs: Vec<usize> = (0..num_runs).rev().collect();
+    let labels: Vec<String> = runs.iter().map(|i| describe_run(&info, *i)).collect();
+    let lhs = interact(dialoguer::Select::new().with_prompt("Compare from which run?").items(&labels).default(1).interact())?;
+    let rhs = interact(dialoguer::Select::new().with_prompt("Against which run?").items(&labels).default(0).interact())?;
+
+    let lhs = crate::types::RunId::Index(runs[lhs]);
+    let rhs = crate::types::RunId::Index(runs[rhs]);
+    if options.filter.is_some() {
+        return print_comparison(&name, lhs, rhs, options);
+    }
+
+    let filter: String = interact(
+        dialoguer::Input::new()
+            .with_prompt("Only show benchmarks matching (a regex, leave empty for all)")
+            .default(String::new())
+            .show_default(false)
+            .interact()
+    )?;
+
+    let filter = crate::cli::parse_filter(Some(filter.trim()))?;
+    let options = crate::compare::CompareOptions { filter, ..options.clone() };
+    print_comparison(&name, lhs, rhs, &options)
+}
+
+pub fn run_individual_benchmark(name: &str, tags: &crate::types::RunTags) -> Result<()> {
+    let (header, _) = lookup_benchmark(name)?;
+    let desc: String = interact(dialoguer::Input::new().with_prompt("What has changed since the last run?").interact())?;
+
+    let exe = &header.source_bin;
+    if !path::Path::new(exe).exists() {
+        return Err(Error::Subprocess { command: exe.to_string(), message: "the executable no longer exists".to_string() });
+    }
+
+    // Fingerprint before running, so a rebuild during a long run isn't recorded against these results
+    let binary = crate::binary::fingerprint(&header.source_bin, &header.source_root);
+    let output = match process::Command::new(exe).arg("--benchmark_format=json").output() {
+        Ok(output) => output,
+        Err(e) => return Err(Error::Subprocess { command: exe.to_string(), message: e.to_string() })
+    };
+
+    if !output.status.success() {
+        let message = format!("{}, {}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+        return Err(Error::Subprocess { command: exe.to_string(), message });
+    }
+
+    let raw: String = String::from_utf8_lossy(&output.stdout).to_string();
+    let new_benches: crate::types::BenchRunResult =
+        serde_json::from_str(&raw).map_err(|e| Error::parse(format!("output of {}", exe), e))?;
+    record_run(name, new_benches, desc, binary, tags)
+}
+
+pub fn import_individual_benchmark(name: &str, file: &str, tags: &crate::types::RunTags, message: Option<&str>) -> Result<()> {
+    lookup_benchmark(name)?;
+    let raw = fs::read_to_string(file).map_err(Error::io(format!("Failed to read {:?}", file)))?;
+    let new_benches: crate::types::BenchRunResult = serde_json::from_str(&raw)
+        .map_err(|e| Error::parse(format!("{:?}, was it written with --benchmark_format=json?", file), e))?;
+
+    let desc = match message {
+        Some(message) => message.to_string(),
+        None => interact(dialoguer::Input::new().with_prompt("What has changed since the last run?").interact())?
+    };
+
+    // We can't know which build produced an imported file, so don't guess.
+    record_run(name, new_benches, desc, None, tags)
+}
+
+fn record_run(
+    name: &str,
+    new_benches: crate::types::BenchRunResult,
+    desc: String,
+    binary: Option<crate::types::BinaryInfo>,
+    tags: &crate::types::RunTags
+) -> Result<()>
+{
+    let lock = crate::config::lock_workspace()?;
+    crate::config::workspace()?.append_run(&lock, name, new_benches, &desc, tags, binary)?;
+    Ok(())
+}
+
+// The run id is resolved under the lock, so it picks the runs as they are when they're changed
+pub fn tag_benchmark_run(name: &str, run_id: &str, tags: &crate::types::RunTags, removals: &[&str]) -> Result<()> {
+    let _lock = crate::config::lock_workspace()?;
+    let run_id = parse_run_id(name, run_id)?;
+    let (_, mut info) = lookup_benchmark(name)?;
+    let (start, end) = run_bounds(&run_id, info.tags.len())?;
+    for index in start..end {
+        let run_tags = &mut info.tags[index];
+        for key in removals {
+            run_tags.remove(*key);
         }
+        run_tags.extend(tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+        println!("Run #{} tags: {}", index, format_tags(run_tags));
     }
 
-    let path = crate::config::get_individual_config_file(&name);
-    match serde_json::to_string_pretty(&info) {
-        Ok(content) => match fs::write(&path, &content) {
-            Ok(_) => {}
-            Err(_) => {
-                println!("Failed to write results to config file.");
-            }
-        },
-        Err(_) => {
-            println!("Failed to write results back to json.");
-            std::process::exit(1);
+    crate::config::workspace()?.write_runs(name, &info)
+}
+
+// The (half open) bounds of the runs a run id refers to, checked against the runs there are
+// in case some were removed since it was resolved
+fn run_bounds(run_id: &crate::types::RunId, num_runs: usize) -> Result<(usize, usize)> {
+    let (start, end) = match run_id {
+        crate::types::RunId::Index(index) => (*index, *index + 1),
+        crate::types::RunId::Range(start, end) => (*start, *end),
+        crate::types::RunId::All => (0, num_runs)
+    };
+
+    if end > num_runs {
+        return Err(Error::InvalidRunId(format!("Run #{} doesn't exist, only {} runs are recorded.", end - 1, num_runs)));
+    }
+    Ok((start, end))
+}
+
+fn short_hash(hash: &str) -> &str {
+    hash.get(..8).unwrap_or(hash)
+}
+
+fn format_tags(tags: &crate::types::RunTags) -> String {
+    tags.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(",")
+}
+
+// A run matches a filter when it has every tag in it, with the same value.
+fn tags_match(tags: &crate::types::RunTags, filter: &crate::types::RunTags) -> bool {
+    filter.iter().all(|(k, v)| tags.get(k) == Some(v))
+}
+
+pub fn create_new_individual_benchmark() -> Result<()> {
+    let name: String = interact(dialoguer::Input::new().with_prompt("Enter a name for the benchmark").interact())?;
+    let src: String = interact(dialoguer::Input::new().with_prompt("Enter a source directory location").interact())?;
+    let bin: String = interact(dialoguer::Input::new().with_prompt("Enter an executable path").interact())?;
+    let desc: String = interact(dialoguer::Input::new().with_prompt("Describe this benchmark").interact())?;
+
+    register_benchmark(
+        &name,
+        crate::types::BenchHeader { source_root: src, source_bin: bin, description: desc, noise_percent: None }
+    )
+}
+
+fn register_benchmark(name: &str, header: crate::types::BenchHeader) -> Result<()> {
+    let lock = crate::config::lock_workspace()?;
+    crate::config::workspace()?.add_benchmark(&lock, name, header)
+}
+
+pub fn discover_benchmarks(root: &str) -> Result<()> {
+    let root = path::Path::new(root).canonicalize().map_err(Error::io(format!("Unable to find directory {:?}", root)))?;
+
+    let tree = crate::discover::find_build_tree(&root);
+    println!("Scanning {:?} for benchmark executables...", tree.build_dir);
+    let found = crate::discover::find_benchmarks(&tree.build_dir);
+    if found.is_empty() {
+        println!("No executables linking google/benchmark were found.");
+        return Ok(());
+    }
+
+    // Registering the same executable twice would only split its runs between two names
+    let registered = crate::config::workspace()?.headers()?;
+    let source_bins: Vec<&str> = registered.values().map(|header| header.source_bin.as_str()).collect();
+    let (known, candidates): (Vec<_>, Vec<_>) =
+        found.into_iter().partition(|candidate| crate::discover::is_registered(&candidate.executable, &source_bins));
+    for candidate in &known {
+        println!("Skipping {:?}, it's already registered.", candidate.executable);
+    }
+    if candidates.is_empty() {
+        println!("Every executable found is already registered.");
+        return Ok(());
+    }
+
+    let labels: Vec<String> = candidates
+        .iter()
+        .map(|c| format!("{} [{} benchmarks]", c.executable.to_string_lossy(), c.tests.len()))
+        .collect();
+    let label_refs: Vec<&str> = labels.iter().map(|l| l.as_str()).collect();
+
+    let selected = interact(
+        dialoguer::Checkboxes::new()
+            .with_prompt("Which executables should be registered? (space to select, enter to confirm)")
+            .items(&label_refs)
+            .interact()
+    )?;
+
+    let source_root = tree.source_root.to_string_lossy().to_string();
+    let mut new = Vec::new();
+    for index in selected {
+        let candidate = &candidates[index];
+        let default_name = candidate.executable.file_stem().map_or("".to_string(), |s| s.to_string_lossy().to_string());
+        let name: String = interact(
+            dialoguer::Input::new()
+                .with_prompt(&format!("Name for {}", candidate.executable.to_string_lossy()))
+                .default(default_name)
+                .interact()
+        )?;
+        let description = format!("{} ({})", candidate.tests.iter().take(3).cloned().collect::<Vec<_>>().join(", "), candidate.tests.len());
+        let header = crate::types::BenchHeader {
+            source_root:   source_root.clone(),
+            source_bin:    candidate.executable.to_string_lossy().to_string(),
+            description,
+            noise_percent: None
+        };
+        new.push((name, header));
+    }
+
+    // All of them are registered under one lock, and a clash with one name shouldn't
+    // stop the rest from being registered
+    let lock = crate::config::lock_workspace()?;
+    let names: Vec<String> = new.iter().map(|(name, _)| name.clone()).collect();
+    let added = crate::config::workspace()?.add_benchmarks(&lock, new)?;
+    for (name, added) in names.iter().zip(added) {
+        if added {
+            println!("Registered {:?}", name);
+        } else {
+            println!("Name {:?} already exists in benchmarks.", name);
         }
     }
+
+    Ok(())
+}
+
+// The run id is resolved under the lock, so another process removing runs meanwhile can't
+// make this remove different ones
+pub fn remove_benchmark_run(name: &str, run_id: &str) -> Result<()> {
+    let _lock = crate::config::lock_workspace()?;
+    let run_id = parse_run_id(name, run_id)?;
+    let workspace = crate::config::workspace()?;
+    let mut info = workspace.runs(name)?;
+    let (start, end) = run_bounds(&run_id, info.benchmarks.len())?;
+    info.benchmarks.drain(start..end);
+    info.commentary.drain(start..end);
+    info.source_hashes.drain(start..end);
+    info.binaries.drain(start..end);
+    info.tags.drain(start..end);
+    info.dates.drain(start..end);
+    workspace.write_runs(name, &info)
+}
+
+pub fn remove_benchmark(name: &str, skip_confirmation: bool) -> Result<()> {
+    let (_, info) = lookup_benchmark(name)?;
+    let prompt = format!("Remove benchmark {:?} and all {} of its recorded runs?", name, info.benchmarks.len());
+    if !skip_confirmation && !interact(dialoguer::Confirmation::new().with_text(&prompt).default(false).interact())? {
+        println!("Ok, leaving {:?} alone.", name);
+        return Ok(());
+    }
+
+    // Drop the catalog entry first, so a failure part way through leaves
+    // an orphaned directory rather than an entry pointing at nothing.
+    let _lock = crate::config::lock_workspace()?;
+    let workspace = crate::config::workspace()?;
+    let mut benches = workspace.headers()?;
+    benches.remove(name);
+    workspace.write_headers(&benches)?;
+
+    let dir = workspace.benchmark_dir(name);
+    fs::remove_dir_all(&dir).map_err(Error::io(format!("Removed {:?} from benchmarks, but failed to delete {:?}", name, dir)))?;
+    println!("Removed benchmark {:?}", name);
+    Ok(())
 }
 
-pub fn remove_benchmark_run_with_prompt() {
-    println!("Current benchmarks (run info command for more info): ");
-    let benches = crate::config::read_top_level_config();
-    for (name, header) in benches {
-        println!(" > {}: {}", name, header.description);
+pub fn rename_benchmark(name: &str, new_name: &str) -> Result<()> {
+    if new_name.is_empty() || new_name.contains(['/', '\\']) || new_name == "." || new_name == ".." {
+        return Err(Error::Invalid(format!("Invalid benchmark name {:?}", new_name)));
     }
 
-    let name = prompt_benchmark_name();
-    let run_id = prompt_run_id(&name);
-    remove_benchmark_run(&name, &run_id);
+    let _lock = crate::config::lock_workspace()?;
+    let workspace = crate::config::workspace()?;
+    let mut benches = workspace.headers()?;
+    let header = match benches.remove(name) {
+        Some(header) => header,
+        None => return Err(Error::unknown_benchmark(name, &benches))
+    };
+
+    let old_dir = workspace.benchmark_dir(name);
+    let new_dir = workspace.benchmark_dir(new_name);
+    if benches.contains_key(new_name) || new_dir.exists() {
+        return Err(Error::Invalid(format!("Name {:?} already exists in benchmarks.", new_name)));
+    }
+
+    // Move the run data first, and put it back if the catalog can't be updated
+    // to match, so the two never disagree.
+    fs::rename(&old_dir, &new_dir).map_err(Error::io(format!("Failed to move {:?} to {:?}", old_dir, new_dir)))?;
+
+    benches.insert(new_name.to_string(), header);
+    if let Err(e) = workspace.write_headers(&benches) {
+        let _ = fs::rename(&new_dir, &old_dir);
+        println!("Failed to update the top level config, rename was rolled back.");
+        return Err(e);
+    }
+
+    println!("Renamed {:?} to {:?}", name, new_name);
+    Ok(())
+}
+
+// Launches $EDITOR on some text in the spirit of `git commit`, None means it was left untouched.
+fn edit_in_editor(text: &str, extension: &str) -> Result<Option<String>> {
+    dialoguer::Editor::new().extension(extension).edit(text).map_err(Error::io("Failed to launch an editor, try setting $EDITOR"))
+}
+
+pub fn edit_benchmark_header(name: &str, edits: crate::types::HeaderEdits) -> Result<()> {
+    let (current, _) = lookup_benchmark(name)?;
+    let no_edits = edits.source_root.is_none() && edits.source_bin.is_none() && edits.description.is_none();
+
+    // The editor can't wait on the lock, so it works on the header as read now
+    let mut replacement = None;
+    if no_edits && edits.noise_percent.is_none() {
+        let text = serde_json::to_string_pretty(&current).map_err(|e| Error::parse("the current header", e))?;
+        let edited = match edit_in_editor(&text, ".json")? {
+            Some(edited) => edited,
+            None => {
+                println!("No changes made.");
+                return Ok(());
+            }
+        };
+
+        replacement = Some(serde_json::from_str(&edited).map_err(|e| Error::parse("the edited header, no changes made", e))?);
+    }
+
+    // Apply the edits to the header as it is once locked, the benchmark could have
+    // been changed or removed since
+    let _lock = crate::config::lock_workspace()?;
+    let workspace = crate::config::workspace()?;
+    let mut benches = workspace.headers()?;
+    let header = match benches.get_mut(name) {
+        Some(header) => header,
+        None => return Err(Error::unknown_benchmark(name, &benches))
+    };
+    if let Some(replacement) = replacement {
+        *header = replacement;
+    }
+    if let Some(source_root) = edits.source_root {
+        header.source_root = source_root;
+    }
+    if let Some(source_bin) = edits.source_bin {
+        header.source_bin = source_bin;
+    }
+    if let Some(description) = edits.description {
+        header.description = description;
+    }
+    if let Some(noise_percent) = edits.noise_percent {
+        header.noise_percent = Some(noise_percent);
+    }
+
+    if !path::Path::new(&header.source_bin).exists() {
+        use colored::*;
+        println!("{}", format!("Warning: executable {:?} does not exist.", header.source_bin).yellow());
+    }
+
+    workspace.write_headers(&benches)?;
+    println!("Updated benchmark {:?}", name);
+    Ok(())
+}
+
+pub fn annotate_benchmark_run(name: &str, run_id: &crate::types::RunId, text: Option<&str>, append: bool) -> Result<()> {
+    let (_, info) = lookup_benchmark(name)?;
+    let index = match run_id {
+        crate::types::RunId::Index(index) => *index,
+        _ => return Err(Error::InvalidRunId("A single run must be given to annotate.".to_string()))
+    };
+
+    let text = match text {
+        Some(text) => text.to_string(),
+        None => {
+            let initial = if append { "" } else { &info.commentary[index] };
+            match edit_in_editor(initial, ".txt")? {
+                Some(edited) => edited.trim().to_string(),
+                None => {
+                    println!("No changes made.");
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    // The editor may have been open a while, so pick up any changes made meanwhile
+    let _lock = crate::config::lock_workspace()?;
+    let workspace = crate::config::workspace()?;
+    let mut info = workspace.runs(name)?;
+    if index >= info.commentary.len() {
+        return Err(Error::InvalidRunId(format!("Run #{} was removed while editing, no changes made.", index)));
+    }
+
+    let commentary = &mut info.commentary[index];
+    if append && !commentary.is_empty() {
+        commentary.push_str("; ");
+        commentary.push_str(&text);
+    } else {
+        *commentary = text;
+    }
+
+    workspace.write_runs(name, &info)
+}
+
+pub fn remove_benchmark_run_with_prompt() -> Result<()> {
+    let name = prompt_benchmark_name()?;
+    let run_id = prompt_run_id(&name)?;
+    remove_benchmark_run(&name, &run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selector::{parse, Selector};
+    use crate::types::{BenchHeader, IndividualBenchInfo};
+
+    #[test]
+    fn check_range_ends() {
+        let header = BenchHeader {
+            source_root:   ".".to_string(),
+            source_bin:    "bench".to_string(),
+            description:   "".to_string(),
+            noise_percent: None
+        };
+        let info = IndividualBenchInfo { benchmarks: vec![Vec::new(); 7], ..IndividualBenchInfo::default() };
+        let bounds = |range: &str| match parse(range) {
+            Some(Selector::Range(start, end, inclusive)) => {
+                crate::io::resolve_range(&header, &info, start.as_deref(), end.as_deref(), inclusive).ok()
+            }
+            _ => panic!("expected a range")
+        };
+
+        assert_eq!(bounds("3..7"), Some((3, 7)));
+        assert_eq!(bounds("0..7"), Some((0, 7)));
+        assert_eq!(bounds("3..=6"), Some((3, 7)));
+        assert_eq!(bounds("3..-1"), Some((3, 6)));
+        assert_eq!(bounds("3.."), Some((3, 7)));
+        // Only an exclusive end may be one past the last run
+        assert_eq!(bounds("3..=7"), None);
+        assert_eq!(bounds("3..8"), None);
+    }
 }
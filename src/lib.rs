@@ -0,0 +1,28 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// The benchviz library, giving typed access to a workspace of recorded
+// benchmarks without going through the CLI. For example:
+//
+//   let workspace = benchviz::workspace::Workspace::locate().unwrap();
+//   for (name, header) in workspace.headers()? {
+//       let runs = workspace.runs(&name)?;
+//       ...
+//   }
+
+pub mod analysis;
+pub mod binary;
+pub mod compare;
+pub mod complexity;
+pub mod discover;
+pub mod error;
+pub mod fuzzy;
+pub mod git;
+pub mod matrix;
+pub mod metric;
+pub mod name;
+pub mod plot;
+pub mod report;
+pub mod scaling;
+pub mod selector;
+pub mod types;
+pub mod workspace;
@@ -1,46 +1,224 @@
 // Copyright 2018 superfunc, see license.txt for usage details.
 
 mod cli;
+mod completions;
 mod config;
-mod git;
 mod io;
-mod types;
 
+use benchviz::{
+    analysis, binary, compare, complexity, discover, error, fuzzy, git, matrix, metric, name, plot, report, scaling, selector,
+    types, workspace
+};
 use clap::clap_app;
 
-fn main() {
-    let matches = clap_app!(benchviz =>
+fn build_app() -> clap::App<'static, 'static> {
+    clap_app!(benchviz =>
        (version: "1.0")
        (author: "superfunc <superfunc@users.noreply.github.com>")
        (about: "A utility for managing C++ benchmarks.")
+       (global_setting: clap::AppSettings::AllowNegativeNumbers)
+       (@arg data_dir: --("data-dir") +takes_value +global
+          "Directory holding recorded benchmarks, overriding $BENCHVIZ_HOME and any .benchviz workspace")
+       (@arg format: --format +takes_value +global possible_values(&["text", "json", "yaml", "markdown"])
+          "Output format for list, info and compare, markdown is for pasting comparisons into pull requests")
+       (after_help: "RUN IDS:
+    Anywhere a run is expected, it may be given as any of
+      3, -1               an index, negative indices count back from the latest run
+      latest, previous    shorthand for -1 and -2
+      @2026-09-01         the latest run recorded on or before a date
+      tag:key[=value]     the latest run with a tag
+      git:<ref or hash>   the latest run of a git ref, or a recorded hash prefix
+      <ref or hash>       the same, for anything that isn't a number
+      3..7, 3..=7, -5..   a range of runs, where accepted
+      *                   every run, where accepted")
+       (@subcommand init =>
+          (about: "Create a workspace in .benchviz, used from this directory and any below it"))
        (@subcommand list =>
           (about: "List available benchmarks"))
        (@subcommand new =>
           (about: "Create a new benchmark"))
+       (@subcommand discover =>
+          (about: "Find google/benchmark executables in a build tree and register them")
+          (@arg path: +required "Build directory, or a source directory containing one"))
        (@subcommand info =>
           (about: "Information on an individual benchmark")
-          (@arg name: +required "Name of benchmark"))
+          (@arg tag: -t --tag +takes_value +multiple number_of_values(1) "Only show runs with this tag (key=value)")
+          (@arg name: +required "Name of benchmark")
+          (@arg runs: "Only show these runs, e.g. 3..7 or -5.."))
        (@subcommand run =>
           (about: "Run another iteration of a benchmark.")
+          (@arg tag: -t --tag +takes_value +multiple number_of_values(1) "Tag the new run (key=value)")
           (@arg name: +required "Name of benchmark"))
+       (@subcommand import =>
+          (about: "Record a run from an existing google/benchmark json output file.")
+          (@arg tag: -t --tag +takes_value +multiple number_of_values(1) "Tag the new run (key=value)")
+          (@arg message: -m --message +takes_value "Commentary for the new run")
+          (@arg name: +required "Name of benchmark")
+          (@arg path: +required "Json file written with --benchmark_format=json or --benchmark_out"))
+       (@subcommand tag =>
+          (about: "Add or remove tags on a run.")
+          (@arg remove: -r --remove +takes_value +multiple number_of_values(1) "Remove the tag with this key")
+          (@arg name: +required "Name of benchmark")
+          (@arg run_id: +required "Run(s) to tag, e.g. 3, latest or 3..7")
+          (@arg tags: +multiple "Tags to set (key=value)"))
        (@subcommand remove =>
           (about: "Remove an entire benchmark, or a particular run.")
+          (@arg yes: -y --yes "Don't ask for confirmation before removing an entire benchmark")
+          (@arg name: "Name of benchmark")
+          (@arg run_id: "Run(s) to remove, e.g. 3, -1 or 3..7; omit to remove the whole benchmark"))
+       (@subcommand rename =>
+          (about: "Rename a benchmark, keeping its recorded runs.")
+          (@arg name: +required "Current name of benchmark")
+          (@arg new_name: +required "New name of benchmark"))
+       (@subcommand edit =>
+          (about: "Edit the details of a benchmark, opens $EDITOR if no fields are given.")
           (@arg name: +required "Name of benchmark")
-          (@arg run_id: +required "Index of the benchmark run (0-indexed)"))
+          (@arg source_root: --("source-root") +takes_value "New source directory location")
+          (@arg source_bin: --("source-bin") +takes_value "New executable path")
+          (@arg description: --description +takes_value "New description")
+          (@arg noise: --noise +takes_value "Percentage changes within which comparisons treat as noise, 1 by default"))
+       (@subcommand annotate =>
+          (about: "Change the commentary on a run, opens $EDITOR if no text is given.")
+          (@arg append: -a --append "Append to the existing commentary rather than replacing it")
+          (@arg name: +required "Name of benchmark")
+          (@arg run_id: +required "The run to annotate, e.g. 3 or latest")
+          (@arg text: "New commentary for the run"))
        (@subcommand compare =>
-          (about: "Compare two runs from a benchmark")
+          (about: "Compare two runs from a benchmark, or of two benchmarks as <name>:<run> <name>:<run>, \
+                   a prompt will guide you if no arguments are given")
+          (@arg name: "Name of benchmark")
+          (@arg run_id_1: "The first run, defaults to previous")
+          (@arg run_id_2: +multiple "The second run, defaults to latest, more runs may follow it")
+          (@arg baseline: --baseline +takes_value
+             "The run percentages are relative to: the first (lhs, the default), the last (rhs), or a position from 1")
+          (@arg sort: --sort +takes_value possible_values(&["file", "name", "delta", "time", "family"])
+             "Order rows by name, biggest regression first, slowest first, or by family then arguments, \
+              rather than as they were run")
+          (@arg filter: --filter +takes_value "Only show benchmarks whose names match this regex")
+          (@arg conditions: --where +takes_value
+             "Only show benchmarks whose names have these parts, e.g. family=BM_Copy,threads=8")
+          (@arg only_changed: --("only-changed") "Only show benchmarks which changed by more than the noise band")
+          (@arg metric: --metric +takes_value +multiple number_of_values(1)
+             "What to compare: real (the default), cpu, both, items/s, bytes/s, throughput or a counter name, may be repeated")
+          (@arg strip_prefix: --("strip-prefix") +takes_value +multiple number_of_values(1)
+             "Between two benchmarks, strip this prefix from names before matching them up")
+          (@arg map: --map +takes_value +multiple number_of_values(1)
+             "Between two benchmarks, match the first's names onto the second's with a glob, e.g. BM_Std_*=BM_Abseil_*")
+          (@arg plot: --plot +takes_value "Also write a heatmap of the changes to this SVG file"))
+       (@subcommand analyze =>
+          (about: "Find the runs where a benchmark's performance stepped up or down")
+          (@arg name: +required "Name of benchmark")
+          (@arg metric: --metric +takes_value +multiple number_of_values(1)
+             "What to analyze: real (the default), cpu, both, items/s, bytes/s or a counter name, may be repeated")
+          (@arg filter: --filter +takes_value "Only analyze benchmarks whose names match this regex"))
+       (@subcommand complexity =>
+          (about: "Show how families of benchmarks such as BM_Sort/8, BM_Sort/64, ... scale with N")
+          (@arg name: +required "Name of benchmark")
+          (@arg run_id_1: "Run to show, the latest by default")
+          (@arg run_id_2: "A later run, to show which families changed complexity since the first")
+          (@arg metric: --metric +takes_value "What to fit: real (the default) or cpu")
+          (@arg filter: --filter +takes_value "Only show families whose names match this regex")
+          (@arg conditions: --where +takes_value "Only fit benchmarks whose names have these parts, e.g. threads=8")
+          (@arg plot: --plot +takes_value "Also write a log-log plot of each family against N to this SVG file"))
+       (@subcommand scaling =>
+          (about: "Show how multithreaded benchmarks speed up with more threads, relative to the fewest they ran on")
+          (@arg name: +required "Name of benchmark")
+          (@arg run_id_1: "Run to show, the latest by default")
+          (@arg run_id_2: "A later run, to show how scaling changed since the first")
+          (@arg metric: --metric +takes_value "What to scale: real (the default), items/s, bytes/s or a counter name")
+          (@arg filter: --filter +takes_value "Only show benchmarks whose names match this regex")
+          (@arg conditions: --where +takes_value "Only show benchmarks whose names have these parts, e.g. family=BM_Copy")
+          (@arg plot: --plot +takes_value "Also write a log-log plot of speedup against threads to this SVG file"))
+       (@subcommand pivot =>
+          (about: "Tabulate a run with one part of its benchmarks' names across, e.g. time against size per thread count")
+          (@arg name: +required "Name of benchmark")
+          (@arg run_id: "Run to tabulate, the latest by default")
+          (@arg on: --on +takes_value +required
+             "Part of the names to put across: a named argument such as threads, arg0 for the first unnamed one, or family")
+          (@arg metric: --metric +takes_value "What to tabulate: real (the default), cpu, items/s, bytes/s or a counter name")
+          (@arg filter: --filter +takes_value "Only tabulate benchmarks whose names match this regex")
+          (@arg conditions: --where +takes_value "Only tabulate benchmarks whose names have these parts, e.g. family=BM_Copy")
+          (@arg plot: --plot +takes_value "Also write a log-log plot of each row against the values across to this SVG file"))
+       (@subcommand outliers =>
+          (about: "List runs noisier than usual, such as those recorded while a machine was throttling")
           (@arg name: +required "Name of benchmark")
-          (@arg run_id_1: +required "Index of the first run")
-          (@arg run_id_2: +required "Index of the second run")))
-    .get_matches();
+          (@arg threshold: --threshold +takes_value
+             "Percentage of noise above which runs are listed, 3 times the benchmark's noise band by default")
+          (@arg exclude: --exclude "Tag the runs listed as excluded, so analyses leave them out"))
+       (@subcommand completions =>
+          (about: "Print a completion script for your shell, e.g. benchviz completions bash > ~/.bash_completion.d/benchviz")
+          (@arg shell: +required possible_values(&["bash", "zsh", "fish"]) "Shell to generate completions for"))
+       (@subcommand candidates =>
+          (@setting Hidden)
+          (about: "Print completion candidates, used by the completion scripts")
+          (@arg kind: +required possible_values(&["names", "runs"]) "What to complete")
+          (@arg name: "Name of benchmark, when completing runs")))
+}
+
+fn main() {
+    let matches = build_app().get_matches();
+    if let Err(e) = run(&matches) {
+        io::print_error(&e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(matches: &clap::ArgMatches) -> error::Result<()> {
+    // Global args given after the subcommand only show up on the subcommand's matches
+    let data_dir = matches.value_of("data_dir").or_else(|| matches.subcommand().1.and_then(|v| v.value_of("data_dir")));
+    if let Some(data_dir) = data_dir {
+        config::set_config_root_dir(data_dir)?;
+    }
+
+    if let Some(format) = matches.value_of("format").or_else(|| matches.subcommand().1.and_then(|v| v.value_of("format"))) {
+        config::set_output_format(format);
+    }
+
+    // Completions must not prompt or print anything beyond what they're asked for
+    if let Some(v) = matches.subcommand_matches("completions") {
+        return completions::generate(build_app(), v.value_of("shell").unwrap());
+    }
+
+    if let Some(v) = matches.subcommand_matches("candidates") {
+        io::print_completion_candidates(v.value_of("kind").unwrap(), v.value_of("name"));
+        return Ok(());
+    }
+
+    if matches.subcommand_matches("init").is_some() {
+        return config::init_local_workspace();
+    }
 
-    config::ensure_dependencies_available();
-    config::ensure_initialized();
+    config::ensure_dependencies_available()?;
+    config::ensure_initialized()?;
 
-    cli::handle_global_query("list", &matches, &io::print_current_benchmarks);
-    cli::handle_global_query("new", &matches, &io::create_new_individual_benchmark);
-    cli::handle_benchmark_query("info", &matches, &io::print_individual_bench_info);
-    cli::handle_benchmark_query("run", &matches, &io::run_individual_benchmark);
-    cli::handle_run_data_query("remove", &matches, &io::remove_benchmark_run, &io::remove_benchmark_run_with_prompt);
-    cli::handle_multi_run_data_query("compare", &matches, &io::print_comparison, &io::print_comparison_with_prompt);
+    cli::handle_global_query("list", matches, &io::print_current_benchmarks)?;
+    cli::handle_global_query("new", matches, &io::create_new_individual_benchmark)?;
+    cli::handle_path_query("discover", matches, &io::discover_benchmarks)?;
+    cli::handle_info_query("info", matches, &io::print_individual_bench_info)?;
+    cli::handle_tagged_benchmark_query("run", matches, &io::run_individual_benchmark)?;
+    cli::handle_import_query("import", matches, &io::import_individual_benchmark)?;
+    cli::handle_tag_query("tag", matches, &io::tag_benchmark_run)?;
+    cli::handle_run_data_query(
+        "remove",
+        matches,
+        &io::remove_benchmark_run,
+        &io::remove_benchmark_run_with_prompt,
+        &io::remove_benchmark
+    )?;
+    cli::handle_rename_query("rename", matches, &io::rename_benchmark)?;
+    cli::handle_edit_query("edit", matches, &io::edit_benchmark_header)?;
+    cli::handle_annotate_query("annotate", matches, &io::annotate_benchmark_run)?;
+    cli::handle_analyze_query("analyze", matches, &io::print_change_points)?;
+    cli::handle_outliers_query("outliers", matches, &io::print_outliers)?;
+    cli::handle_complexity_query("complexity", matches, &io::print_complexity)?;
+    cli::handle_pivot_query("pivot", matches, &io::print_pivot)?;
+    cli::handle_scaling_query("scaling", matches, &io::print_scaling)?;
+    cli::handle_multi_run_data_query(
+        "compare",
+        matches,
+        &io::print_comparison,
+        &io::print_comparison_with_prompt,
+        &io::print_cross_comparison,
+        &io::print_matrix_comparison
+    )
 }
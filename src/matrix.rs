@@ -0,0 +1,192 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing comparisons of several runs at once, as for release
+// sign-off, where every run is shown relative to one chosen baseline run.
+
+use serde::Serialize;
+
+use crate::compare::{Change, CompareOptions, Comparison, SortOrder};
+use crate::metric::Metric;
+use crate::types::IndividualBenchInfo;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MatrixRow {
+    pub name:          String,
+    pub metric:        Metric,
+    pub unit:          String,
+    // One per run, None where the run didn't have this benchmark
+    pub values:        Vec<Option<f64>>,
+    // Relative to the baseline run's value, so None for the baseline itself
+    pub percent_diffs: Vec<Option<f64>>
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Matrix {
+    pub runs:     Vec<usize>,
+    // Index into runs
+    pub baseline: usize,
+    pub rows:     Vec<MatrixRow>
+}
+
+impl MatrixRow {
+    fn new(name: &str, metric: &Metric, unit: String, values: Vec<Option<f64>>, baseline: usize) -> MatrixRow {
+        let percent_diffs = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| match (value, values[baseline]) {
+                (Some(value), Some(base)) if i != baseline => Some(100.0 * (value - base) / base),
+                _ => None
+            })
+            .collect();
+
+        MatrixRow { name: name.to_string(), metric: metric.clone(), unit, values, percent_diffs }
+    }
+
+    // How the run in this column changed from the baseline
+    pub fn change(&self, column: usize, noise_percent: f64) -> Change {
+        match self.regression_percent(column) {
+            Some(percent) if percent.abs() >= noise_percent => {
+                if percent > 0.0 {
+                    Change::Regression
+                } else {
+                    Change::Improvement
+                }
+            }
+            _ => Change::Unchanged
+        }
+    }
+
+    // The percentage change in this column, positive when it's for the worse
+    pub fn regression_percent(&self, column: usize) -> Option<f64> {
+        let percent = self.percent_diffs[column].filter(|percent| !percent.is_nan())?;
+        Some(if self.metric.higher_is_better() { -percent } else { percent })
+    }
+
+    fn worst_regression(&self) -> f64 {
+        (0..self.values.len()).filter_map(|column| self.regression_percent(column)).fold(f64::NEG_INFINITY, f64::max)
+    }
+}
+
+impl Matrix {
+    // As Comparison::arranged, where a row counts as changed if any run changed, and sorting
+    // by delta puts the worst regression in any run first
+    pub fn arranged(mut self, options: &CompareOptions, noise_percent: f64) -> Matrix {
+        if let Some(filter) = &options.filter {
+            self.rows.retain(|row| filter.is_match(&row.name));
+        }
+
+        if let Some(conditions) = &options.conditions {
+            self.rows.retain(|row| conditions.is_match(&row.name));
+        }
+
+        if options.only_changed {
+            self.rows.retain(|row| (0..row.values.len()).any(|column| row.change(column, noise_percent) != Change::Unchanged));
+        }
+
+        let baseline = self.baseline;
+        let descending = |a: f64, b: f64| b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal);
+        match options.sort {
+            SortOrder::File => {}
+            SortOrder::Name => self.rows.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortOrder::Delta => self.rows.sort_by(|a, b| descending(a.worst_regression(), b.worst_regression())),
+            SortOrder::Time => self.rows.sort_by(|a, b| {
+                descending(a.values[baseline].unwrap_or(f64::NEG_INFINITY), b.values[baseline].unwrap_or(f64::NEG_INFINITY))
+            }),
+            SortOrder::Family => self.rows.sort_by(|a, b| crate::compare::natural_cmp(&a.name, &b.name))
+        }
+
+        self
+    }
+}
+
+// A two run comparison as a matrix, e.g. to plot it
+impl From<&Comparison> for Matrix {
+    fn from(comparison: &Comparison) -> Matrix {
+        let baseline = if comparison.baseline == crate::compare::Baseline::Lhs { 0 } else { 1 };
+        let rows = comparison
+            .rows
+            .iter()
+            .map(|row| {
+                let values = vec![Some(row.lhs_value), Some(row.rhs_value)];
+                MatrixRow::new(&row.name, &row.metric, row.unit.clone(), values, baseline)
+            })
+            .collect();
+
+        Matrix { runs: vec![comparison.lhs, comparison.rhs], baseline, rows }
+    }
+}
+
+// Compare any number of runs by index, matching results by name in the order they first
+// appear. None if any of the runs doesn't exist, or the baseline isn't one of them.
+pub fn compare_matrix(info: &IndividualBenchInfo, runs: &[usize], baseline: usize, metrics: &[Metric]) -> Option<Matrix> {
+    let results = runs.iter().map(|run| info.benchmarks.get(*run)).collect::<Option<Vec<_>>>()?;
+    if baseline >= runs.len() {
+        return None;
+    }
+
+    let mut names: Vec<&str> = Vec::new();
+    for result in results.iter().flat_map(|results| results.iter()) {
+        if !names.contains(&result.name.as_str()) {
+            names.push(&result.name);
+        }
+    }
+
+    let mut rows = Vec::new();
+    for name in names {
+        let found: Vec<_> = results.iter().map(|results| results.iter().find(|result| result.name == name)).collect();
+        for metric in metrics {
+            let values: Vec<Option<f64>> = found.iter().map(|result| result.and_then(|result| metric.value(result))).collect();
+            // Units come from whichever run has the metric, if any do
+            let unit = found.iter().flatten().find(|result| metric.value(result).is_some()).map(|result| metric.unit(result));
+            if let Some(unit) = unit {
+                rows.push(MatrixRow::new(name, metric, unit, values, baseline));
+            }
+        }
+    }
+
+    Some(Matrix { runs: runs.to_vec(), baseline, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::{Change, CompareOptions, SortOrder};
+    use crate::matrix::compare_matrix;
+    use crate::metric::Metric;
+    use crate::types::{BenchResult, IndividualBenchInfo};
+
+    #[test]
+    fn check_matrix() {
+        let run = |times: &[(&str, f64)]| -> Vec<BenchResult> {
+            times
+                .iter()
+                .map(|(name, time)| BenchResult::timed(name, *time))
+                .collect()
+        };
+
+        let info = IndividualBenchInfo {
+            benchmarks: vec![
+                run(&[("BM_A", 100.0), ("BM_B", 10.0)]),
+                run(&[("BM_A", 110.0), ("BM_B", 10.0)]),
+                run(&[("BM_B", 5.0), ("BM_C", 1.0)]),
+            ],
+            ..Default::default()
+        };
+
+        let matrix = compare_matrix(&info, &[0, 1, 2], 1, &[Metric::RealTime]).unwrap();
+        let names: Vec<&str> = matrix.rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, vec!["BM_A", "BM_B", "BM_C"]);
+        assert_eq!(matrix.rows[0].values, vec![Some(100.0), Some(110.0), None]);
+        assert_eq!(matrix.rows[1].percent_diffs, vec![Some(0.0), None, Some(-50.0)]);
+        assert_eq!(matrix.rows[2].percent_diffs, vec![None, None, None]);
+        assert_eq!(matrix.rows[1].change(2, 1.0), Change::Improvement);
+        assert!(compare_matrix(&info, &[0, 3], 0, &[Metric::RealTime]).is_none());
+        assert!(compare_matrix(&info, &[0, 1], 2, &[Metric::RealTime]).is_none());
+
+        let options = CompareOptions { sort: SortOrder::Delta, only_changed: true, ..Default::default() };
+        let names: Vec<String> = matrix.arranged(&options, 1.0).rows.into_iter().map(|row| row.name).collect();
+        assert_eq!(names, vec!["BM_B", "BM_A"]);
+
+        let matrix = compare_matrix(&info, &[0, 1, 2], 0, &[Metric::RealTime]).unwrap();
+        assert_eq!(matrix.rows[0].change(1, 1.0), Change::Regression);
+    }
+}
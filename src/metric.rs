@@ -0,0 +1,149 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing the measurements which can be read out of a benchmark
+// result, so comparisons (and anything else looking at results) agree on what
+// "faster" means for each.
+
+use crate::types::BenchResult;
+
+// Bytes go up in binary multiples, as google/benchmark's own console output does, items in decimal ones
+const BYTE_UNITS: &[&str] = &["B/s", "KiB/s", "MiB/s", "GiB/s", "TiB/s"];
+const ITEM_UNITS: &[&str] = &["items/s", "k items/s", "M items/s", "G items/s", "T items/s"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Metric {
+    RealTime,
+    CpuTime,
+    ItemsPerSecond,
+    BytesPerSecond,
+    // A user counter, as set through state.counters
+    Counter(String)
+}
+
+impl Metric {
+    // Parse a --metric value, anything unrecognised is taken as a counter name. Both is
+    // shorthand for real and cpu time side by side, throughput for bytes/s and items/s.
+    pub fn parse(raw: &str) -> Vec<Metric> {
+        match raw {
+            "real" | "real_time" => vec![Metric::RealTime],
+            "cpu" | "cpu_time" => vec![Metric::CpuTime],
+            "both" => vec![Metric::RealTime, Metric::CpuTime],
+            "items/s" | "items_per_second" => vec![Metric::ItemsPerSecond],
+            "bytes/s" | "bytes_per_second" => vec![Metric::BytesPerSecond],
+            "throughput" => vec![Metric::BytesPerSecond, Metric::ItemsPerSecond],
+            counter => vec![Metric::Counter(counter.to_string())]
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Metric::RealTime => "real",
+            Metric::CpuTime => "cpu",
+            Metric::ItemsPerSecond => "items/s",
+            Metric::BytesPerSecond => "bytes/s",
+            Metric::Counter(name) => name
+        }
+    }
+
+    pub fn is_time(&self) -> bool {
+        matches!(self, Metric::RealTime | Metric::CpuTime)
+    }
+
+    // Throughput is better the higher it goes, times (and counters, for lack of
+    // anything saying otherwise) the lower.
+    pub fn higher_is_better(&self) -> bool {
+        matches!(self, Metric::ItemsPerSecond | Metric::BytesPerSecond)
+    }
+
+    // None when the result doesn't have this metric, e.g. a benchmark which never
+    // called SetItemsProcessed, or a complexity fit which has no measurements at all.
+    pub fn value(&self, result: &BenchResult) -> Option<f64> {
+        if result.is_complexity() {
+            return None;
+        }
+
+        match self {
+            Metric::RealTime => Some(result.real_time),
+            Metric::CpuTime => Some(result.cpu_time),
+            Metric::ItemsPerSecond => result.items_per_second,
+            Metric::BytesPerSecond => result.bytes_per_second,
+            Metric::Counter(name) => result.extra.get(name).and_then(|v| v.as_f64())
+        }
+    }
+
+    pub fn unit(&self, result: &BenchResult) -> String {
+        match self {
+            Metric::RealTime | Metric::CpuTime => result.time_unit.clone(),
+            Metric::ItemsPerSecond | Metric::BytesPerSecond => self.name().to_string(),
+            Metric::Counter(_) => "".to_string()
+        }
+    }
+
+    // Throughput scaled to whichever multiple reads best, e.g. 1.5e9 bytes/s is 1.397 GiB/s
+    fn scaled(&self, value: f64) -> Option<(f64, &'static str)> {
+        let (base, units) = match self {
+            Metric::BytesPerSecond => (1024.0, BYTE_UNITS),
+            Metric::ItemsPerSecond => (1000.0, ITEM_UNITS),
+            _ => return None
+        };
+        let mut scaled = (value, 0);
+        while scaled.0.abs() >= base && scaled.1 + 1 < units.len() {
+            scaled = (scaled.0 / base, scaled.1 + 1);
+        }
+        Some((scaled.0, units[scaled.1]))
+    }
+
+    // A value as people read it, in the unit given unless it's a throughput
+    pub fn format(&self, value: f64, unit: &str) -> String {
+        match self.scaled(value) {
+            Some((value, unit)) => format!("{:.3} {}", value, unit),
+            None => format!("{:.3} {}", value, unit).trim_end().to_string()
+        }
+    }
+
+    // As format, but for a difference, so always signed
+    pub fn format_diff(&self, value: f64, unit: &str) -> String {
+        match self.scaled(value) {
+            Some((value, unit)) => format!("{:+.3} {}", value, unit),
+            None => format!("{:+.3} {}", value, unit).trim_end().to_string()
+        }
+    }
+}
+
+impl serde::Serialize for Metric {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Metric;
+    use crate::types::BenchResult;
+
+    #[test]
+    fn check_metric() {
+        let result: BenchResult = serde_json::from_str(
+            r#"{"name": "BM_Copy/64", "run_name": "BM_Copy/64", "iterations": 10, "real_time": 2.0, "cpu_time": 1.5,
+                "time_unit": "ns", "bytes_per_second": 32.0, "misses": 7}"#
+        )
+        .unwrap();
+
+        assert_eq!(Metric::parse("both"), vec![Metric::RealTime, Metric::CpuTime]);
+        assert_eq!(Metric::CpuTime.value(&result), Some(1.5));
+        assert_eq!(Metric::parse("bytes/s")[0].value(&result), Some(32.0));
+        assert_eq!(Metric::parse("misses")[0].value(&result), Some(7.0));
+        assert_eq!(Metric::ItemsPerSecond.value(&result), None);
+        assert_eq!(Metric::Counter("run_name".to_string()).value(&result), None);
+        assert!(Metric::BytesPerSecond.higher_is_better() && !Metric::RealTime.higher_is_better());
+        assert!(!result.extra.contains_key("bytes_per_second"));
+
+        assert_eq!(Metric::parse("throughput"), vec![Metric::BytesPerSecond, Metric::ItemsPerSecond]);
+        assert_eq!(Metric::BytesPerSecond.format(1.5 * 1024.0 * 1024.0 * 1024.0, "bytes/s"), "1.500 GiB/s");
+        assert_eq!(Metric::BytesPerSecond.format(512.0, "bytes/s"), "512.000 B/s");
+        assert_eq!(Metric::ItemsPerSecond.format(2.5e6, "items/s"), "2.500 M items/s");
+        assert_eq!(Metric::ItemsPerSecond.format_diff(-3e3, "items/s"), "-3.000 k items/s");
+        assert_eq!(Metric::RealTime.format(12.0, "ns"), "12.000 ns");
+        assert_eq!(Metric::Counter("misses".to_string()).format_diff(7.0, ""), "+7.000");
+    }
+}
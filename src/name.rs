@@ -0,0 +1,309 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing the structure google/benchmark encodes in a benchmark's
+// name, e.g. BM_Copy/1024/threads:8/real_time is the BM_Copy family with one
+// argument, run on eight threads, timed by wall clock. Results can then be
+// picked out, grouped and pivoted on any part of it.
+
+use serde::Serialize;
+
+use crate::metric::Metric;
+use crate::types::BenchResult;
+
+// Statistics google/benchmark appends to a name when running repetitions
+const AGGREGATES: &[&str] = &["mean", "median", "stddev", "cv", "BigO", "RMS"];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BenchName {
+    pub family:    String,
+    // From ->Arg(), ->Range() and the like, in order
+    pub args:      Vec<i64>,
+    // From ->ArgNames(), or options such as threads:8 and min_time:0.5, in order
+    pub named:     Vec<(String, String)>,
+    // Options without a value, such as real_time or process_time
+    pub modifiers: Vec<String>,
+    // Such as mean, when this is a statistic over repetitions
+    pub aggregate: Option<String>
+}
+
+impl BenchName {
+    pub fn parse(name: &str) -> BenchName {
+        let mut aggregate = None;
+        let mut name = name;
+        for suffix in AGGREGATES {
+            if let Some(stripped) = name.strip_suffix(&format!("_{}", suffix)) {
+                aggregate = Some(suffix.to_string());
+                name = stripped;
+                break;
+            }
+        }
+
+        let mut parts = name.split('/');
+        let mut parsed = BenchName { family: parts.next().unwrap_or_default().to_string(), aggregate, ..Default::default() };
+        for part in parts {
+            if let Ok(arg) = part.parse::<i64>() {
+                parsed.args.push(arg);
+            } else if let Some(pos) = part.find(':') {
+                parsed.named.push((part[..pos].to_string(), part[pos + 1..].to_string()));
+            } else {
+                parsed.modifiers.push(part.to_string());
+            }
+        }
+
+        parsed
+    }
+
+    // A part of the name by key: family, aggregate, arg0, arg1, ... for unnamed arguments, or
+    // the name of a named one such as threads
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "family" => Some(self.family.clone()),
+            "aggregate" => self.aggregate.clone(),
+            _ => match key.strip_prefix("arg").and_then(|index| index.parse::<usize>().ok()) {
+                Some(index) => self.args.get(index).map(|arg| arg.to_string()),
+                None => self.named.iter().find(|(name, _)| name == key).map(|(_, value)| value.clone())
+            }
+        }
+    }
+
+    // The same name without the part a key refers to, as for grouping on the rest of it
+    pub fn without(&self, key: &str) -> BenchName {
+        let mut name = self.clone();
+        match (key, key.strip_prefix("arg").and_then(|index| index.parse::<usize>().ok())) {
+            ("family", _) => name.family.clear(),
+            ("aggregate", _) => name.aggregate = None,
+            (_, Some(index)) if index < name.args.len() => {
+                name.args.remove(index);
+            }
+            _ => name.named.retain(|(name, _)| name != key)
+        }
+        name
+    }
+
+    // Orders names as a person would: by family, then arguments by value rather than as text,
+    // so BM_Copy/8 comes before BM_Copy/64.
+    pub fn natural_cmp(&self, other: &BenchName) -> std::cmp::Ordering {
+        let value = |raw: &str| raw.parse::<f64>().ok();
+        let named = |a: &[(String, String)], b: &[(String, String)]| {
+            for ((a_name, a_value), (b_name, b_value)) in a.iter().zip(b) {
+                let ordering = a_name.cmp(b_name).then_with(|| match (value(a_value), value(b_value)) {
+                    (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+                    _ => a_value.cmp(b_value)
+                });
+                if ordering != std::cmp::Ordering::Equal {
+                    return ordering;
+                }
+            }
+            a.len().cmp(&b.len())
+        };
+
+        self.family
+            .cmp(&other.family)
+            .then_with(|| self.args.cmp(&other.args))
+            .then_with(|| named(&self.named, &other.named))
+            .then_with(|| self.modifiers.cmp(&other.modifiers))
+            .then_with(|| self.aggregate.cmp(&other.aggregate))
+    }
+}
+
+// Back into the form google/benchmark writes, with arguments, then named arguments, then modifiers
+impl std::fmt::Display for BenchName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.family)?;
+        for arg in &self.args {
+            write!(f, "/{}", arg)?;
+        }
+        for (name, value) in &self.named {
+            write!(f, "/{}:{}", name, value)?;
+        }
+        for modifier in &self.modifiers {
+            write!(f, "/{}", modifier)?;
+        }
+        match &self.aggregate {
+            Some(aggregate) => write!(f, "_{}", aggregate),
+            None => Ok(())
+        }
+    }
+}
+
+// Conditions on the parts of names, such as family=BM_Copy,threads=8, all of which must hold.
+// A modifier=real_time condition holds when the name has that modifier.
+#[derive(Clone, Debug, Default)]
+pub struct Conditions {
+    pub conditions: Vec<(String, String)>
+}
+
+impl Conditions {
+    // None when any condition isn't key=value
+    pub fn parse(raw: &str) -> Option<Conditions> {
+        let conditions = raw
+            .split(',')
+            .map(|condition| {
+                let pos = condition.find('=')?;
+                let (key, value) = (condition[..pos].trim(), condition[pos + 1..].trim());
+                Some((key.to_string(), value.to_string())).filter(|_| !key.is_empty())
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Conditions { conditions })
+    }
+
+    pub fn matches(&self, name: &BenchName) -> bool {
+        self.conditions.iter().all(|(key, value)| match key.as_str() {
+            "modifier" => name.modifiers.contains(value),
+            _ => name.get(key).as_ref() == Some(value)
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.matches(&BenchName::parse(name))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PivotRow {
+    // The name without the key pivoted on, e.g. BM_Copy/threads:8 pivoting on arg0
+    pub name:   String,
+    // One per column, None where there was no such benchmark
+    pub values: Vec<Option<f64>>
+}
+
+// Results tabulated with one part of their names across, such as time against size for each
+// thread count
+#[derive(Clone, Debug, Serialize)]
+pub struct Pivot {
+    pub key:     String,
+    pub metric:  Metric,
+    pub unit:    String,
+    // Each value of the key, ordered by value
+    pub columns: Vec<String>,
+    pub rows:    Vec<PivotRow>
+}
+
+impl Pivot {
+    // Each row as points for a plot, leaving out any values which aren't numbers
+    pub fn points(&self, row: &PivotRow) -> Vec<(f64, f64)> {
+        self.columns
+            .iter()
+            .zip(&row.values)
+            .filter_map(|(column, value)| Some((column.parse::<f64>().ok()?, (*value)?)))
+            .collect()
+    }
+}
+
+// Pivot the results of a run on a key, as for BenchName::get. Results without it, aggregates
+// and those without the metric are left out, and repetitions count as their median. Rows are
+// in the order they first appear.
+pub fn pivot(results: &[BenchResult], key: &str, metric: &Metric) -> Option<Pivot> {
+    let mut columns: Vec<String> = Vec::new();
+    // Name, and the values in each column
+    type Row = (String, Vec<(String, Vec<f64>)>);
+    let mut rows: Vec<Row> = Vec::new();
+    for result in results {
+        let name = BenchName::parse(&result.name);
+        let (column, value) = match (name.get(key), metric.value(result)) {
+            (Some(column), Some(value)) if name.aggregate.is_none() => (column, value),
+            _ => continue
+        };
+
+        if !columns.contains(&column) {
+            columns.push(column.clone());
+        }
+        let row = name.without(key).to_string();
+        let index = match rows.iter().position(|(name, _)| *name == row) {
+            Some(index) => index,
+            None => {
+                rows.push((row, Vec::new()));
+                rows.len() - 1
+            }
+        };
+        match rows[index].1.iter_mut().find(|(existing, _)| *existing == column) {
+            Some((_, values)) => values.push(value),
+            None => rows[index].1.push((column, vec![value]))
+        }
+    }
+
+    let unit = results.iter().find(|result| metric.value(result).is_some()).map(|result| metric.unit(result))?;
+    columns.sort_by(|a, b| match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+        _ => a.cmp(b)
+    });
+    let rows = rows
+        .into_iter()
+        .map(|(name, mut cells)| PivotRow {
+            name,
+            values: columns
+                .iter()
+                .map(|column| {
+                    let (_, values) = cells.iter_mut().find(|(existing, _)| existing == column)?;
+                    Some(crate::analysis::median(values))
+                })
+                .collect()
+        })
+        .collect();
+
+    Some(Pivot { key: key.to_string(), metric: metric.clone(), unit, columns, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Metric;
+    use crate::name::{pivot, BenchName, Conditions};
+    use crate::types::BenchResult;
+
+    #[test]
+    fn check_bench_name() {
+        let name = BenchName::parse("BM_Copy<char>/1024/threads:8/real_time_mean");
+        assert_eq!(name.family, "BM_Copy<char>");
+        assert_eq!(name.args, vec![1024]);
+        assert_eq!(name.named, vec![("threads".to_string(), "8".to_string())]);
+        assert_eq!(name.modifiers, vec!["real_time"]);
+        assert_eq!(name.aggregate.as_deref(), Some("mean"));
+        assert_eq!(name.to_string(), "BM_Copy<char>/1024/threads:8/real_time_mean");
+        assert_eq!(name.get("arg0").as_deref(), Some("1024"));
+        assert_eq!(name.get("threads").as_deref(), Some("8"));
+        assert_eq!(name.get("arg1"), None);
+        assert_eq!(name.without("arg0").to_string(), "BM_Copy<char>/threads:8/real_time_mean");
+        assert_eq!(name.without("family").without("threads").to_string(), "/1024/real_time_mean");
+        assert_eq!(BenchName::parse("BM_Find_BigO").family, "BM_Find");
+
+        let mut names: Vec<BenchName> = ["BM_B/8", "BM_A/64", "BM_A/8", "BM_A/8/threads:16", "BM_A/8/threads:2"]
+            .iter()
+            .map(|name| BenchName::parse(name))
+            .collect();
+        names.sort_by(|a, b| a.natural_cmp(b));
+        let sorted: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        assert_eq!(sorted, vec!["BM_A/8", "BM_A/8/threads:2", "BM_A/8/threads:16", "BM_A/64", "BM_B/8"]);
+
+        let conditions = Conditions::parse("family=BM_Copy, threads=8,modifier=real_time").unwrap();
+        assert!(conditions.is_match("BM_Copy/64/threads:8/real_time"));
+        assert!(!conditions.is_match("BM_Copy/64/threads:4/real_time"));
+        assert!(!conditions.is_match("BM_Copy/64/threads:8"));
+        assert!(Conditions::parse("family").is_none() && Conditions::parse("=8").is_none());
+    }
+
+    #[test]
+    fn check_pivot() {
+        let result = BenchResult::timed;
+        let results = vec![
+            result("BM_Copy/1024/threads:1", 100.0),
+            result("BM_Copy/64/threads:1", 10.0),
+            result("BM_Copy/64/threads:1", 14.0),
+            result("BM_Copy/64/threads:2", 6.0),
+            result("BM_Copy/64/threads:1_mean", 1000.0),
+            result("BM_Other", 1.0),
+        ];
+
+        let table = pivot(&results, "arg0", &Metric::RealTime).unwrap();
+        assert_eq!(table.columns, vec!["64", "1024"]);
+        let names: Vec<&str> = table.rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, vec!["BM_Copy/threads:1", "BM_Copy/threads:2"]);
+        assert_eq!(table.rows[0].values, vec![Some(12.0), Some(100.0)]);
+        assert_eq!(table.rows[1].values, vec![Some(6.0), None]);
+        assert_eq!(table.points(&table.rows[1]), vec![(64.0, 6.0)]);
+
+        let table = pivot(&results, "threads", &Metric::RealTime).unwrap();
+        assert_eq!(table.columns, vec!["1", "2"]);
+        assert_eq!(table.rows[1].name, "BM_Copy/64");
+        assert!(pivot(&results, "threads", &Metric::ItemsPerSecond).is_none());
+    }
+}
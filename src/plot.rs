@@ -0,0 +1,358 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing plots of recorded runs, written as standalone SVG so they
+// can be opened in a browser or attached to a pull request without any
+// plotting libraries.
+
+use crate::compare::Change;
+use crate::complexity::FamilyComplexity;
+use crate::matrix::Matrix;
+use crate::scaling::FamilyScaling;
+
+const CELL_WIDTH: usize = 110;
+const CELL_HEIGHT: usize = 24;
+// Rough width of a character at the font size used, to size the name column
+const CHAR_WIDTH: usize = 7;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Red for regressions and green for improvements, stronger the bigger the change
+// relative to the biggest shown, white within the noise band.
+fn heat(regression_percent: f64, scale: f64, change: Change) -> String {
+    let strength = (regression_percent.abs() / scale).min(1.0);
+    let fade = |channel: f64| (255.0 - (255.0 - channel) * strength).round() as u8;
+    match change {
+        Change::Regression => format!("rgb({},{},{})", fade(215.0), fade(48.0), fade(39.0)),
+        Change::Improvement => format!("rgb({},{},{})", fade(26.0), fade(152.0), fade(80.0)),
+        Change::Unchanged => "rgb(255,255,255)".to_string()
+    }
+}
+
+// A heatmap of a matrix, one row per benchmark and metric and one column per run,
+// each cell colored by its change from the baseline column.
+pub fn heatmap(title: &str, columns: &[String], matrix: &Matrix, noise_percent: f64) -> String {
+    let several = matrix.rows.iter().any(|row| row.metric != matrix.rows[0].metric);
+    let labels: Vec<String> = matrix
+        .rows
+        .iter()
+        .map(|row| if several { format!("{} ({})", row.name, row.metric.name()) } else { row.name.clone() })
+        .collect();
+
+    let name_width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0) * CHAR_WIDTH + 20;
+    let top = 2 * CELL_HEIGHT;
+    let width = std::cmp::max(name_width + columns.len() * CELL_WIDTH, title.chars().count() * CHAR_WIDTH + 20);
+    let height = top + matrix.rows.len() * CELL_HEIGHT + 10;
+    let scale = matrix
+        .rows
+        .iter()
+        .flat_map(|row| (0..row.values.len()).filter_map(move |column| row.regression_percent(column)))
+        .fold(noise_percent, |scale, percent| scale.max(percent.abs()));
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+        width, height
+    );
+    svg.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n", width, height));
+    svg.push_str(&format!("<text x=\"10\" y=\"{}\" font-weight=\"bold\">{}</text>\n", CELL_HEIGHT / 2 + 6, escape(title)));
+
+    for (i, column) in columns.iter().enumerate() {
+        let weight = if i == matrix.baseline { " font-weight=\"bold\"" } else { "" };
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\"{}>{}</text>\n",
+            name_width + i * CELL_WIDTH + CELL_WIDTH / 2,
+            top - 8,
+            weight,
+            escape(column)
+        ));
+    }
+
+    for (r, (row, label)) in matrix.rows.iter().zip(labels.iter()).enumerate() {
+        let y = top + r * CELL_HEIGHT;
+        svg.push_str(&format!("<text x=\"10\" y=\"{}\">{}</text>\n", y + CELL_HEIGHT / 2 + 4, escape(label)));
+        for column in 0..row.values.len() {
+            let x = name_width + column * CELL_WIDTH;
+            let (fill, text) = match (row.values[column], row.regression_percent(column)) {
+                (None, _) => ("rgb(238,238,238)".to_string(), "-".to_string()),
+                (Some(_), None) if column == matrix.baseline => ("rgb(255,255,255)".to_string(), "baseline".to_string()),
+                (Some(_), None) => ("rgb(238,238,238)".to_string(), "no baseline".to_string()),
+                (Some(_), Some(percent)) => {
+                    let text = format!("{:+.1}%", row.percent_diffs[column].unwrap_or_default());
+                    (heat(percent, scale, row.change(column, noise_percent)), text)
+                }
+            };
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"rgb(204,204,204)\"/>\n",
+                x, y, CELL_WIDTH, CELL_HEIGHT, fill
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+                x + CELL_WIDTH / 2,
+                y + CELL_HEIGHT / 2 + 4,
+                text
+            ));
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// A line on a chart, drawn through its points in order
+#[derive(Clone, Debug)]
+pub struct Series {
+    // Left out of the legend when empty
+    pub label:  String,
+    // Into the palette, so related series can share a color
+    pub color:  usize,
+    pub points: Vec<(f64, f64)>,
+    pub dashed: bool
+}
+
+const PALETTE: &[&str] = &[
+    "rgb(31,119,180)",
+    "rgb(255,127,14)",
+    "rgb(44,160,44)",
+    "rgb(214,39,40)",
+    "rgb(148,103,189)",
+    "rgb(140,86,75)",
+    "rgb(227,119,194)",
+    "rgb(127,127,127)"
+];
+const CHART_WIDTH: usize = 560;
+const CHART_HEIGHT: usize = 360;
+const CHART_LEFT: usize = 80;
+const CHART_TOP: usize = 40;
+
+fn tick_label(exponent: i32) -> String {
+    if (0..6).contains(&exponent) {
+        format!("{}", 10u64.pow(exponent as u32))
+    } else {
+        format!("1e{}", exponent)
+    }
+}
+
+// Whole decades covering the values, at least one
+fn decades(values: impl Iterator<Item = f64>) -> (i32, i32) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| (min.min(v), max.max(v)));
+    if !min.is_finite() {
+        return (0, 1);
+    }
+
+    let (low, high) = (min.log10().floor() as i32, max.log10().ceil() as i32);
+    (low, std::cmp::max(high, low + 1))
+}
+
+// A line chart with both axes on a log scale, where power laws are straight lines
+// whose slope is the power. Points which aren't positive can't be shown and are skipped.
+pub fn log_log(title: &str, x_label: &str, y_label: &str, series: &[Series]) -> String {
+    let positive = |(x, y): &&(f64, f64)| *x > 0.0 && *y > 0.0;
+    let points = || series.iter().flat_map(|series| series.points.iter().filter(positive));
+    let (x_low, x_high) = decades(points().map(|(x, _)| *x));
+    let (y_low, y_high) = decades(points().map(|(_, y)| *y));
+    let to_x = |x: f64| CHART_LEFT as f64 + (x.log10() - x_low as f64) / (x_high - x_low) as f64 * CHART_WIDTH as f64;
+    let to_y = |y: f64| {
+        (CHART_TOP + CHART_HEIGHT) as f64 - (y.log10() - y_low as f64) / (y_high - y_low) as f64 * CHART_HEIGHT as f64
+    };
+
+    let legend_width = series.iter().map(|series| series.label.chars().count()).max().unwrap_or(0) * CHAR_WIDTH + 50;
+    let width = std::cmp::max(CHART_LEFT + CHART_WIDTH + legend_width, title.chars().count() * CHAR_WIDTH + 20);
+    let height = std::cmp::max(CHART_TOP + CHART_HEIGHT + 50, CHART_TOP + series.len() * 18 + 10);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+        width, height
+    );
+    svg.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n", width, height));
+    svg.push_str(&format!("<text x=\"10\" y=\"{}\" font-weight=\"bold\">{}</text>\n", CELL_HEIGHT / 2 + 6, escape(title)));
+
+    // Gridlines and labels at each decade
+    for exponent in x_low..=x_high {
+        let x = to_x(10f64.powi(exponent));
+        svg.push_str(&format!(
+            "<line x1=\"{0:.1}\" y1=\"{1}\" x2=\"{0:.1}\" y2=\"{2}\" stroke=\"rgb(230,230,230)\"/>\n",
+            x,
+            CHART_TOP,
+            CHART_TOP + CHART_HEIGHT
+        ));
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+            x,
+            CHART_TOP + CHART_HEIGHT + 16,
+            tick_label(exponent)
+        ));
+    }
+    for exponent in y_low..=y_high {
+        let y = to_y(10f64.powi(exponent));
+        svg.push_str(&format!(
+            "<line x1=\"{0}\" y1=\"{1:.1}\" x2=\"{2}\" y2=\"{1:.1}\" stroke=\"rgb(230,230,230)\"/>\n",
+            CHART_LEFT,
+            y,
+            CHART_LEFT + CHART_WIDTH
+        ));
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\n",
+            CHART_LEFT - 6,
+            y + 4.0,
+            tick_label(exponent)
+        ));
+    }
+    svg.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"rgb(120,120,120)\"/>\n",
+        CHART_LEFT, CHART_TOP, CHART_WIDTH, CHART_HEIGHT
+    ));
+    svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+        CHART_LEFT + CHART_WIDTH / 2,
+        CHART_TOP + CHART_HEIGHT + 40,
+        escape(x_label)
+    ));
+    svg.push_str(&format!(
+        "<text x=\"16\" y=\"{0}\" text-anchor=\"middle\" transform=\"rotate(-90 16 {0})\">{1}</text>\n",
+        CHART_TOP + CHART_HEIGHT / 2,
+        escape(y_label)
+    ));
+
+    let mut legend = 0;
+    for line in series {
+        let color = PALETTE[line.color % PALETTE.len()];
+        let dash = if line.dashed { " stroke-dasharray=\"6,4\"" } else { "" };
+        let coordinates: Vec<(f64, f64)> = line.points.iter().filter(positive).map(|(x, y)| (to_x(*x), to_y(*y))).collect();
+        let path: Vec<String> = coordinates.iter().map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+        svg.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"{}/>\n",
+            path.join(" "),
+            color,
+            dash
+        ));
+        if !line.dashed {
+            for (x, y) in &coordinates {
+                svg.push_str(&format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"/>\n", x, y, color));
+            }
+        }
+
+        if !line.label.is_empty() {
+            let (x, y) = (CHART_LEFT + CHART_WIDTH + 16, CHART_TOP + legend * 18 + 8);
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{2}\" x2=\"{1}\" y2=\"{2}\" stroke=\"{3}\" stroke-width=\"2\"{4}/>\n",
+                x,
+                x + 20,
+                y,
+                color,
+                dash
+            ));
+            svg.push_str(&format!("<text x=\"{}\" y=\"{}\">{}</text>\n", x + 26, y + 4, escape(&line.label)));
+            legend += 1;
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Each family's values against N, one list of families per run. A single run has its
+// fitted curves dashed; given more, the earlier runs are dashed so the latest stands out.
+pub fn complexity(title: &str, columns: &[String], runs: &[Vec<FamilyComplexity>], y_label: &str) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for family in runs.iter().flatten() {
+        if !names.contains(&family.name.as_str()) {
+            names.push(&family.name);
+        }
+    }
+
+    let mut series = Vec::new();
+    for (run, (column, families)) in columns.iter().zip(runs).enumerate() {
+        for family in families {
+            let color = names.iter().position(|name| *name == family.name).unwrap_or_default();
+            let class = family.complexity().map(|complexity| complexity.name().to_string()).unwrap_or_default();
+            let label = if runs.len() > 1 { format!("{} {}", column, family.name) } else { family.name.clone() };
+            let label = format!("{} {}", label, class);
+            series.push(Series { label, color, points: family.points.clone(), dashed: run + 1 < runs.len() });
+
+            if let (1, Some(fit)) = (runs.len(), &family.fitted) {
+                let points = family.points.iter().map(|(n, _)| (*n, fit.estimate(*n))).collect();
+                series.push(Series { label: "".to_string(), color, points, dashed: true });
+            }
+        }
+    }
+
+    log_log(title, "N", y_label, &series)
+}
+
+// Each family's speedup against its thread count, one list of families per run, with
+// perfect scaling as a grey dashed line. Given more than one run, the earlier are dashed.
+pub fn scaling(title: &str, columns: &[String], runs: &[Vec<FamilyScaling>]) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for family in runs.iter().flatten() {
+        if !names.contains(&family.name.as_str()) {
+            names.push(&family.name);
+        }
+    }
+
+    let mut series = Vec::new();
+    let counts = || runs.iter().flatten().flat_map(|family| family.points.iter().map(|point| point.threads));
+    if let (Some(fewest), Some(most)) = (counts().min(), counts().max()) {
+        let ideal = vec![(fewest as f64, 1.0), (most as f64, most as f64 / fewest as f64)];
+        series.push(Series { label: "ideal".to_string(), color: PALETTE.len() - 1, points: ideal, dashed: true });
+    }
+    for (run, (column, families)) in columns.iter().zip(runs).enumerate() {
+        for family in families {
+            let color = names.iter().position(|name| *name == family.name).unwrap_or_default() % (PALETTE.len() - 1);
+            let label = if runs.len() > 1 { format!("{} {}", column, family.name) } else { family.name.clone() };
+            let points = family.points.iter().map(|point| (point.threads as f64, point.speedup)).collect();
+            series.push(Series { label, color, points, dashed: run + 1 < runs.len() });
+        }
+    }
+
+    log_log(title, "threads", "speedup", &series)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::compare_matrix;
+    use crate::metric::Metric;
+    use crate::plot::{heatmap, log_log, Series};
+    use crate::types::{BenchResult, IndividualBenchInfo};
+
+    #[test]
+    fn check_heatmap() {
+        let run = |time: f64| {
+            vec![BenchResult::timed("BM_Map<int, int>", time)]
+        };
+        let info = IndividualBenchInfo { benchmarks: vec![run(10.0), run(20.0), run(5.0)], ..Default::default() };
+        let matrix = compare_matrix(&info, &[0, 1, 2], 0, &[Metric::RealTime]).unwrap();
+        let columns = vec!["#0".to_string(), "#1".to_string(), "#2".to_string()];
+
+        let svg = heatmap("maps & sets", &columns, &matrix, 1.0);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("maps &amp; sets") && svg.contains("BM_Map&lt;int, int&gt;"));
+        assert!(svg.contains("+100.0%") && svg.contains("-50.0%") && svg.contains("baseline"));
+        // The biggest change is the most saturated
+        assert!(svg.contains("fill=\"rgb(215,48,39)\""));
+    }
+
+    #[test]
+    fn check_log_log() {
+        let series = vec![
+            Series {
+                label:  "BM_Sort<&> O(NlgN)".to_string(),
+                color:  0,
+                points: vec![(8.0, 24.0), (4096.0, 49152.0)],
+                dashed: false
+            },
+            Series { label: "".to_string(), color: 0, points: vec![(8.0, 20.0), (4096.0, 50000.0)], dashed: true },
+            Series { label: "BM_Empty".to_string(), color: 1, points: vec![(0.0, 1.0), (8.0, 0.0)], dashed: false },
+        ];
+
+        let svg = log_log("sorting", "N", "real time (ns)", &series);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("BM_Sort&lt;&amp;&gt; O(NlgN)") && svg.contains("real time (ns)"));
+        assert_eq!(svg.matches("<polyline").count(), 3);
+        assert_eq!(svg.matches("stroke-dasharray").count(), 1);
+        assert_eq!(svg.matches("<circle").count(), 2);
+        // Decades from 1 to 10000 along the bottom, 10 to 100000 up the side
+        assert!(svg.contains(">1</text>") && svg.contains(">10000</text>") && svg.contains(">100000</text>"));
+        assert!(log_log("", "", "", &[]).contains(">10</text>"));
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing the structured output of the read commands, as printed by
+// --format json or yaml. These are a stable interface for scripts: fields may
+// be added, but changing or removing any bumps SCHEMA_VERSION. The readme
+// documents each of them.
+
+use serde::Serialize;
+
+use crate::analysis::{RunNoise, SeriesAnalysis};
+use crate::compare::{Baseline, Comparison, ComparisonRow};
+use crate::complexity::{ComplexityChange, FamilyComplexity};
+use crate::matrix::{Matrix, MatrixRow};
+use crate::metric::Metric;
+use crate::name::Pivot;
+use crate::scaling::FamilyScaling;
+use crate::types::{BenchHeader, BinaryInfo, IndividualBenchInfo, RunTags, TopLevelBenchInfo};
+
+// 2: compare's percent_diff is relative to the baseline, lhs by default, rather than always rhs
+// 3: compare's rows have a metric, with lhs_time, rhs_time and time_unit now lhs_value, rhs_value and unit
+// 4: compare's git_range is omitted when the benchmarks have different source roots
+pub const SCHEMA_VERSION: u32 = 4;
+
+#[derive(Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub name:        String,
+    pub description: String,
+    pub source_root: String,
+    pub source_bin:  String
+}
+
+// Output of list
+#[derive(Debug, Serialize)]
+pub struct ListReport {
+    pub schema_version: u32,
+    pub workspace:      String,
+    pub benchmarks:     Vec<BenchmarkReport>
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub index:       usize,
+    // Empty when unknown, as for runs recorded by older versions
+    pub date:        String,
+    pub source_hash: String,
+    pub commentary:  String,
+    pub tags:        RunTags,
+    pub binary:      Option<BinaryInfo>,
+    // Only in info and outliers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise:       Option<RunNoise>
+}
+
+// Output of info
+#[derive(Debug, Serialize)]
+pub struct InfoReport {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub benchmark:      BenchmarkReport,
+    pub runs:           Vec<RunReport>
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitRange {
+    pub source_root: String,
+    pub from:        String,
+    pub to:          String
+}
+
+// Output of compare
+#[derive(Debug, Serialize)]
+pub struct CompareReport {
+    pub schema_version: u32,
+    pub name:           String,
+    // Only when the runs are of two different benchmarks, name is then the first's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rhs_name:       Option<String>,
+    pub lhs:            RunReport,
+    pub rhs:            RunReport,
+    // Only when both benchmarks are built from the same source root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_range:      Option<GitRange>,
+    pub baseline:       Baseline,
+    pub noise_percent:  f64,
+    pub rows:           Vec<ComparisonRow>,
+    // Results in only one of the runs, by name
+    pub unmatched:      Vec<String>
+}
+
+// Output of compare given more than two runs
+#[derive(Debug, Serialize)]
+pub struct MatrixReport {
+    pub schema_version: u32,
+    pub name:           String,
+    pub runs:           Vec<RunReport>,
+    // Index into runs
+    pub baseline:       usize,
+    pub noise_percent:  f64,
+    pub rows:           Vec<MatrixRow>
+}
+
+// Output of outliers
+#[derive(Debug, Serialize)]
+pub struct OutliersReport {
+    pub schema_version:    u32,
+    pub name:              String,
+    pub threshold_percent: f64,
+    // Whether the runs were just tagged as excluded
+    pub excluded:          bool,
+    pub runs:              Vec<RunReport>
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunComplexity {
+    #[serde(flatten)]
+    pub run:      RunReport,
+    pub families: Vec<FamilyComplexity>
+}
+
+// Output of complexity
+#[derive(Debug, Serialize)]
+pub struct ComplexityReport {
+    pub schema_version: u32,
+    pub name:           String,
+    pub metric:         Metric,
+    pub runs:           Vec<RunComplexity>,
+    // Between the first two runs, empty given just one
+    pub changes:        Vec<ComplexityChange>
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunScaling {
+    #[serde(flatten)]
+    pub run:      RunReport,
+    pub families: Vec<FamilyScaling>
+}
+
+// Output of scaling
+#[derive(Debug, Serialize)]
+pub struct ScalingReport {
+    pub schema_version: u32,
+    pub name:           String,
+    pub metric:         Metric,
+    pub runs:           Vec<RunScaling>
+}
+
+// Output of pivot
+#[derive(Debug, Serialize)]
+pub struct PivotReport {
+    pub schema_version: u32,
+    pub name:           String,
+    pub run:            RunReport,
+    #[serde(flatten)]
+    pub pivot:          Pivot
+}
+
+// Output of analyze
+#[derive(Debug, Serialize)]
+pub struct AnalysisReport {
+    pub schema_version: u32,
+    pub name:           String,
+    pub noise_percent:  f64,
+    pub benchmarks:     Vec<SeriesAnalysis>,
+    // Each run either side of a change point, by index
+    pub runs:           Vec<RunReport>
+}
+
+fn benchmark_report(name: &str, header: &BenchHeader) -> BenchmarkReport {
+    BenchmarkReport {
+        name:        name.to_string(),
+        description: header.description.clone(),
+        source_root: header.source_root.clone(),
+        source_bin:  header.source_bin.clone()
+    }
+}
+
+pub fn run_report(info: &IndividualBenchInfo, index: usize) -> RunReport {
+    RunReport {
+        index,
+        date: info.dates.get(index).cloned().unwrap_or_default(),
+        source_hash: info.source_hashes[index].clone(),
+        commentary: info.commentary[index].clone(),
+        tags: info.tags.get(index).cloned().unwrap_or_default(),
+        binary: info.binaries.get(index).cloned().flatten(),
+        noise: None
+    }
+}
+
+fn noisy_run_report(info: &IndividualBenchInfo, index: usize, noise: &[RunNoise]) -> RunReport {
+    RunReport { noise: noise.get(index).cloned(), ..run_report(info, index) }
+}
+
+// Benchmarks are sorted by name, so output is stable between calls
+pub fn list_report(workspace: &str, benches: &TopLevelBenchInfo) -> ListReport {
+    let mut benchmarks: Vec<BenchmarkReport> = benches.iter().map(|(name, header)| benchmark_report(name, header)).collect();
+    benchmarks.sort_by(|a, b| a.name.cmp(&b.name));
+    ListReport { schema_version: SCHEMA_VERSION, workspace: workspace.to_string(), benchmarks }
+}
+
+pub fn info_report(name: &str, header: &BenchHeader, info: &IndividualBenchInfo, runs: &[usize]) -> InfoReport {
+    let noise = crate::analysis::run_noise(info);
+    InfoReport {
+        schema_version: SCHEMA_VERSION,
+        benchmark:      benchmark_report(name, header),
+        runs:           runs.iter().map(|index| noisy_run_report(info, *index, &noise)).collect()
+    }
+}
+
+pub fn outliers_report(
+    name: &str,
+    info: &IndividualBenchInfo,
+    noise: &[RunNoise],
+    runs: &[usize],
+    threshold_percent: f64,
+    excluded: bool
+) -> OutliersReport
+{
+    OutliersReport {
+        schema_version: SCHEMA_VERSION,
+        name: name.to_string(),
+        threshold_percent,
+        excluded,
+        runs: runs.iter().map(|index| noisy_run_report(info, *index, noise)).collect()
+    }
+}
+
+// A benchmark with its runs, one side of a comparison
+pub struct ComparedBenchmark<'a> {
+    pub name:   &'a str,
+    pub header: &'a BenchHeader,
+    pub info:   &'a IndividualBenchInfo
+}
+
+impl ComparedBenchmark<'_> {
+    // Whether the runs of both can be diffed in the same repository
+    pub fn shares_source(&self, other: &ComparedBenchmark) -> bool {
+        self.header.source_root == other.header.source_root
+    }
+}
+
+// The noise band is the first benchmark's
+pub fn compare_report(lhs: &ComparedBenchmark, rhs: &ComparedBenchmark, comparison: Comparison) -> CompareReport {
+    let git_range = GitRange { source_root: lhs.header.source_root.clone(), from: comparison.lhs_hash, to: comparison.rhs_hash };
+    CompareReport {
+        schema_version: SCHEMA_VERSION,
+        name:           lhs.name.to_string(),
+        rhs_name:       Some(rhs.name.to_string()).filter(|name| name != lhs.name),
+        lhs:            run_report(lhs.info, comparison.lhs),
+        rhs:            run_report(rhs.info, comparison.rhs),
+        git_range:      Some(git_range).filter(|_| lhs.shares_source(rhs)),
+        baseline:       comparison.baseline,
+        noise_percent:  lhs.header.noise_band(),
+        rows:           comparison.rows,
+        unmatched:      comparison.unmatched
+    }
+}
+
+pub fn matrix_report(name: &str, header: &BenchHeader, info: &IndividualBenchInfo, matrix: Matrix) -> MatrixReport {
+    MatrixReport {
+        schema_version: SCHEMA_VERSION,
+        name:           name.to_string(),
+        runs:           matrix.runs.iter().map(|index| run_report(info, *index)).collect(),
+        baseline:       matrix.baseline,
+        noise_percent:  header.noise_band(),
+        rows:           matrix.rows
+    }
+}
+
+pub fn analysis_report(
+    name: &str,
+    header: &BenchHeader,
+    info: &IndividualBenchInfo,
+    analyses: Vec<SeriesAnalysis>
+) -> AnalysisReport
+{
+    let mut runs: Vec<usize> = analyses
+        .iter()
+        .flat_map(|analysis| analysis.change_points.iter().flat_map(|point| vec![point.previous_run, point.run]))
+        .collect();
+    runs.sort_unstable();
+    runs.dedup();
+
+    AnalysisReport {
+        schema_version: SCHEMA_VERSION,
+        name:           name.to_string(),
+        noise_percent:  header.noise_band(),
+        benchmarks:     analyses,
+        runs:           runs.into_iter().map(|index| run_report(info, index)).collect()
+    }
+}
+
+pub fn complexity_report(
+    name: &str,
+    info: &IndividualBenchInfo,
+    metric: &Metric,
+    runs: &[usize],
+    families: Vec<Vec<FamilyComplexity>>,
+    changes: Vec<ComplexityChange>
+) -> ComplexityReport
+{
+    ComplexityReport {
+        schema_version: SCHEMA_VERSION,
+        name: name.to_string(),
+        metric: metric.clone(),
+        runs: runs.iter().zip(families).map(|(run, families)| RunComplexity { run: run_report(info, *run), families }).collect(),
+        changes
+    }
+}
+
+pub fn pivot_report(name: &str, info: &IndividualBenchInfo, run: usize, pivot: Pivot) -> PivotReport {
+    PivotReport { schema_version: SCHEMA_VERSION, name: name.to_string(), run: run_report(info, run), pivot }
+}
+
+pub fn scaling_report(
+    name: &str,
+    info: &IndividualBenchInfo,
+    metric: &Metric,
+    runs: &[usize],
+    families: Vec<Vec<FamilyScaling>>
+) -> ScalingReport
+{
+    ScalingReport {
+        schema_version: SCHEMA_VERSION,
+        name: name.to_string(),
+        metric: metric.clone(),
+        runs: runs.iter().zip(families).map(|(run, families)| RunScaling { run: run_report(info, *run), families }).collect()
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing how multithreaded benchmarks, those registered with
+// ->Threads() or ->ThreadRange(), scale with the number of threads running
+// them.
+
+use serde::Serialize;
+
+use crate::metric::Metric;
+use crate::name::BenchName;
+use crate::types::BenchResult;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ScalingPoint {
+    pub threads:    i64,
+    pub value:      f64,
+    // How many times better than with the fewest threads, one normally
+    pub speedup:    f64,
+    // Speedup per thread added, as a percentage, where 100% is perfect scaling
+    pub efficiency: f64
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FamilyScaling {
+    // The benchmarks' name without their thread count, e.g. BM_Copy/64 for BM_Copy/64/threads:8
+    pub name:   String,
+    pub unit:   String,
+    // In increasing number of threads
+    pub points: Vec<ScalingPoint>
+}
+
+impl FamilyScaling {
+    pub fn at(&self, threads: i64) -> Option<&ScalingPoint> {
+        self.points.iter().find(|point| point.threads == threads)
+    }
+}
+
+// google/benchmark writes threads for every result, older runs only have it in the name
+fn threads(result: &BenchResult, name: &BenchName) -> Option<i64> {
+    result.threads.or_else(|| name.get("threads")?.parse().ok())
+}
+
+// Each family of a run which was run on more than one thread count, in the order they
+// first appear. Google/benchmark reports times per iteration across all threads, so they
+// shrink as threads are added; throughput grows. Repetitions count as their median.
+pub fn scaling(results: &[BenchResult], metric: &Metric) -> Vec<FamilyScaling> {
+    // Name, unit, and the values at each thread count
+    type Family = (String, String, Vec<(i64, Vec<f64>)>);
+    let mut families: Vec<Family> = Vec::new();
+    for result in results {
+        let name = BenchName::parse(&result.name);
+        let (threads, value) = match (threads(result, &name), metric.value(result)) {
+            (Some(threads), Some(value)) if name.aggregate.is_none() && threads > 0 => (threads, value),
+            _ => continue
+        };
+
+        let family = name.without("threads").to_string();
+        let index = match families.iter().position(|(name, _, _)| *name == family) {
+            Some(index) => index,
+            None => {
+                families.push((family, metric.unit(result), Vec::new()));
+                families.len() - 1
+            }
+        };
+        let counts = &mut families[index].2;
+        match counts.iter_mut().find(|(count, _)| *count == threads) {
+            Some((_, values)) => values.push(value),
+            None => counts.push((threads, vec![value]))
+        }
+    }
+
+    families
+        .into_iter()
+        .filter(|(_, _, counts)| counts.len() > 1)
+        .map(|(name, unit, mut counts)| {
+            counts.sort_by_key(|(threads, _)| *threads);
+            let values: Vec<(i64, f64)> =
+                counts.into_iter().map(|(threads, mut values)| (threads, crate::analysis::median(&mut values))).collect();
+            let (base_threads, base) = values[0];
+            let points = values
+                .into_iter()
+                .map(|(threads, value)| {
+                    let speedup = if metric.higher_is_better() { value / base } else { base / value };
+                    let efficiency = 100.0 * speedup * base_threads as f64 / threads as f64;
+                    ScalingPoint { threads, value, speedup, efficiency }
+                })
+                .collect();
+            FamilyScaling { name, unit, points }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metric::Metric;
+    use crate::scaling::scaling;
+    use crate::types::BenchResult;
+
+    #[test]
+    fn check_scaling() {
+        let result = |name: &str, threads: Option<i64>, time: f64| BenchResult {
+            threads,
+            items_per_second: Some(1e9 / time),
+            ..BenchResult::timed(name, time)
+        };
+        let results = vec![
+            result("BM_Copy/64/threads:1", Some(1), 100.0),
+            result("BM_Copy/64/threads:2", Some(2), 50.0),
+            result("BM_Copy/64/threads:4", None, 40.0),
+            result("BM_Copy/64/threads:4", None, 30.0),
+            result("BM_Copy/64/threads:4_mean", None, 35.0),
+            result("BM_Lock/threads:2", Some(2), 10.0),
+            result("BM_Lock/threads:8", Some(8), 20.0),
+            result("BM_Single", Some(1), 1.0),
+        ];
+
+        let families = scaling(&results, &Metric::RealTime);
+        let names: Vec<&str> = families.iter().map(|family| family.name.as_str()).collect();
+        assert_eq!(names, vec!["BM_Copy/64", "BM_Lock"]);
+        let threads: Vec<i64> = families[0].points.iter().map(|point| point.threads).collect();
+        assert_eq!(threads, vec![1, 2, 4]);
+        assert_eq!(families[0].at(2).unwrap().speedup, 2.0);
+        assert_eq!(families[0].at(2).unwrap().efficiency, 100.0);
+        assert!((families[0].at(4).unwrap().speedup - 100.0 / 35.0).abs() < 1e-9);
+        // Relative to the fewest threads run, here two
+        assert_eq!(families[1].at(8).unwrap().speedup, 0.5);
+        assert_eq!(families[1].at(8).unwrap().efficiency, 12.5);
+
+        let throughput = scaling(&results, &Metric::ItemsPerSecond);
+        assert!((throughput[0].at(2).unwrap().speedup - 2.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing the run selector language, used anywhere a run id is
+// accepted on the command line. Selectors are parsed here, and resolved
+// against a benchmark's recorded runs in io.
+//
+//   *                 every run
+//   3, -1             an index, negative indices count back from the latest
+//   latest, previous  shorthand for -1 and -2
+//   @2026-09-01       the latest run recorded on or before a date
+//   tag:key[=value]   the latest run with a tag (value defaults to true)
+//   3..7, 3..=7       a range of runs, either end may be omitted or any of the above
+//   git:rev           a git ref or hash prefix, matched against source hashes
+//   anything else     the same, unless it's a number, which is always an index
+
+pub enum Selector {
+    All,
+    Index(i64),
+    // As given, and the last moment it covers
+    Date(String, DateKey),
+    Tag(String, String),
+    Revision(String),
+    Range(Option<Box<Selector>>, Option<Box<Selector>>, bool)
+}
+
+pub fn parse(raw: &str) -> Option<Selector> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+
+    if let Some(pos) = raw.find("..") {
+        let (start, end) = (&raw[..pos], &raw[pos + 2..]);
+        let (end, inclusive) = match end.strip_prefix('=') {
+            Some(end) => (end, true),
+            None => (end, false)
+        };
+
+        let parse_end = |end: &str| if end.is_empty() { Some(None) } else { parse_single(end).map(|s| Some(Box::new(s))) };
+        return Some(Selector::Range(parse_end(start)?, parse_end(end)?, inclusive));
+    }
+
+    if raw == "*" {
+        return Some(Selector::All);
+    }
+
+    parse_single(raw)
+}
+
+fn parse_single(raw: &str) -> Option<Selector> {
+    if raw == "*" || raw.contains("..") {
+        return None;
+    }
+
+    if raw == "latest" {
+        return Some(Selector::Index(-1));
+    }
+
+    if raw == "previous" {
+        return Some(Selector::Index(-2));
+    }
+
+    if let Ok(index) = raw.parse::<i64>() {
+        return Some(Selector::Index(index));
+    }
+
+    if let Some(date) = raw.strip_prefix('@') {
+        return parse_date(date, true).map(|key| Selector::Date(date.trim().to_string(), key));
+    }
+
+    if let Some(rev) = raw.strip_prefix("git:") {
+        return if rev.is_empty() { None } else { Some(Selector::Revision(rev.to_string())) };
+    }
+
+    if let Some(tag) = raw.strip_prefix("tag:") {
+        let (key, value) = match tag.find('=') {
+            Some(pos) => (&tag[..pos], &tag[pos + 1..]),
+            None => (tag, "true")
+        };
+        return if key.is_empty() { None } else { Some(Selector::Tag(key.to_string(), value.to_string())) };
+    }
+
+    Some(Selector::Revision(raw.to_string()))
+}
+
+// Year, month, day, hour, minute and second, so dates order chronologically
+pub type DateKey = (u32, u32, u32, u32, u32, u32);
+
+// google/benchmark has written dates as "2019-01-01 12:00:00", in ISO 8601 form
+// ("2019-01-01T12:00:00+01:00", whose offset is ignored) and, in older versions, as
+// "%x %X" ("01/31/19 12:00:00"). A date without a time is its first moment, or its
+// last with end_of_day. None for anything else, which then matches no selector.
+pub fn parse_date(date: &str, end_of_day: bool) -> Option<DateKey> {
+    let date = date.trim();
+    let (day, time) = match date.find(['T', ' ']) {
+        Some(pos) => (&date[..pos], Some(date[pos + 1..].trim())),
+        None => (date, None)
+    };
+
+    let numbers = |raw: &str, separator: char| raw.split(separator).map(|n| n.parse::<u32>().ok()).collect::<Option<Vec<u32>>>();
+    let (year, month, day) = match (numbers(day, '-'), numbers(day, '/')) {
+        (Some(ymd), _) => match ymd.as_slice() {
+            [year, month, day] if *year >= 1000 => (*year, *month, *day),
+            _ => return None
+        },
+        (_, Some(mdy)) => match mdy.as_slice() {
+            // Two digit years are 1969 to 2068, as POSIX strptime reads %y
+            [month, day, year] if *year < 69 => (2000 + year, *month, *day),
+            [month, day, year] if *year < 100 => (1900 + year, *month, *day),
+            [month, day, year] => (*year, *month, *day),
+            _ => return None
+        },
+        _ => return None
+    };
+
+    let (hour, minute, second) = match time {
+        // Up to any fraction of a second or offset
+        Some(time) => {
+            let clock = &time[..time.find(|c: char| !c.is_ascii_digit() && c != ':').unwrap_or(time.len())];
+            match numbers(clock, ':')?.as_slice() {
+                [hour, minute] => (*hour, *minute, 0),
+                [hour, minute, second] => (*hour, *minute, *second),
+                _ => return None
+            }
+        }
+        None if end_of_day => (23, 59, 59),
+        None => (0, 0, 0)
+    };
+
+    let valid = (1..=12).contains(&month) && (1..=31).contains(&day) && hour < 24 && minute < 60 && second < 61;
+    Some((year, month, day, hour, minute, second)).filter(|_| valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selector::{parse, parse_date, Selector};
+
+    #[test]
+    fn check_indices() {
+        assert!(matches!(parse("3"), Some(Selector::Index(3))));
+        assert!(matches!(parse("-1"), Some(Selector::Index(-1))));
+        assert!(matches!(parse("latest"), Some(Selector::Index(-1))));
+        assert!(matches!(parse("previous"), Some(Selector::Index(-2))));
+        assert!(matches!(parse("*"), Some(Selector::All)));
+        assert!(parse("").is_none());
+    }
+
+    #[test]
+    fn check_named() {
+        assert!(matches!(parse("@2026-09-01"), Some(Selector::Date(ref d, (2026, 9, 1, 23, 59, 59))) if d == "2026-09-01"));
+        assert!(parse("@yesterday").is_none());
+        assert!(matches!(parse("tag:baseline"), Some(Selector::Tag(ref k, ref v)) if k == "baseline" && v == "true"));
+        assert!(matches!(parse("tag:branch=main"), Some(Selector::Tag(ref k, ref v)) if k == "branch" && v == "main"));
+        assert!(matches!(parse("v1.2"), Some(Selector::Revision(ref r)) if r == "v1.2"));
+        // A hash prefix of only digits would otherwise be an index
+        assert!(matches!(parse("1234567"), Some(Selector::Index(1234567))));
+        assert!(matches!(parse("git:1234567"), Some(Selector::Revision(ref r)) if r == "1234567"));
+        assert!(parse("git:").is_none());
+        assert!(parse("tag:").is_none());
+    }
+
+    #[test]
+    fn check_dates() {
+        assert_eq!(parse_date("2019-01-31 12:30:05", false), Some((2019, 1, 31, 12, 30, 5)));
+        assert_eq!(parse_date("2019-01-31T12:30:05+01:00", false), Some((2019, 1, 31, 12, 30, 5)));
+        assert_eq!(parse_date("2019-01-31T12:30:05.123Z", false), Some((2019, 1, 31, 12, 30, 5)));
+        assert_eq!(parse_date("01/31/19 12:30:05", false), Some((2019, 1, 31, 12, 30, 5)));
+        assert_eq!(parse_date("12/31/98 23:00:00", false), Some((1998, 12, 31, 23, 0, 0)));
+        assert_eq!(parse_date("2019-01-31", false), Some((2019, 1, 31, 0, 0, 0)));
+        assert_eq!(parse_date("01/31/2019", true), Some((2019, 1, 31, 23, 59, 59)));
+        // Text order would put every January date before December of an earlier year
+        assert!(parse_date("01/02/20 00:00:00", false) > parse_date("12/31/19 00:00:00", false));
+        assert_eq!(parse_date("", false), None);
+        assert_eq!(parse_date("31/01/19", false), None);
+        assert_eq!(parse_date("2019-13-01", false), None);
+        assert_eq!(parse_date("Thu Jan 31 2019", false), None);
+    }
+
+    #[test]
+    fn check_ranges() {
+        match parse("3..7") {
+            Some(Selector::Range(Some(start), Some(end), false)) => {
+                assert!(matches!(*start, Selector::Index(3)));
+                assert!(matches!(*end, Selector::Index(7)));
+            }
+            _ => panic!("expected a range")
+        }
+
+        assert!(matches!(parse("..=latest"), Some(Selector::Range(None, Some(_), true))));
+        assert!(matches!(parse("-3.."), Some(Selector::Range(Some(_), None, false))));
+        assert!(parse("1..2..3").is_none());
+    }
+}
@@ -9,17 +9,59 @@
 // config directory for this program, so most derive it from serde.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 // TODO: Cleanup naming of types, seems a bit inconsistent
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+// The _BigO and _RMS aggregates google/benchmark adds for ->Complexity() benchmarks
+// have no times, or even iterations, so those default to zero.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct BenchResult {
-    pub name:       String,
-    pub iterations: i64,
-    pub real_time:  f64,
-    pub cpu_time:   f64,
-    pub time_unit:  String
+    pub name:             String,
+    #[serde(default)]
+    pub iterations:       i64,
+    #[serde(default)]
+    pub real_time:        f64,
+    #[serde(default)]
+    pub cpu_time:         f64,
+    #[serde(default)]
+    pub time_unit:        String,
+    // How many threads ran the benchmark, as set with ->Threads()
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads:          Option<i64>,
+    // Throughput, for benchmarks which called SetBytesProcessed or SetItemsProcessed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_per_second: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items_per_second: Option<f64>,
+    // Everything else google/benchmark reported, such as user counters, kept as is
+    #[serde(flatten)]
+    pub extra:            BTreeMap<String, serde_json::Value>
+}
+
+impl BenchResult {
+    // Such as mean, stddev or BigO, when this isn't a result in its own right
+    pub fn aggregate_name(&self) -> Option<&str> {
+        self.extra.get("aggregate_name").and_then(|v| v.as_str())
+    }
+
+    pub fn is_complexity(&self) -> bool {
+        matches!(self.aggregate_name(), Some("BigO") | Some("RMS"))
+    }
+
+    // A single iteration taking time ns of both real and cpu time, for tests to build on with
+    // BenchResult { threads: Some(2), ..BenchResult::timed(name, time) }
+    #[cfg(test)]
+    pub fn timed(name: &str, time: f64) -> BenchResult {
+        BenchResult {
+            name:       name.to_string(),
+            iterations: 1,
+            real_time:  time,
+            cpu_time:   time,
+            time_unit:  "ns".to_string(),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -46,29 +88,75 @@ pub struct BenchRunResult {
     pub benchmarks: Vec<BenchResult>
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct IndividualBenchInfo {
     // TODO: Update context per machine, just in case :)
     // TODO: Add git changes
     pub context:       Option<EnvironmentInfo>,
     pub commentary:    Vec<String>,
     pub benchmarks:    Vec<Vec<BenchResult>>,
-    pub source_hashes: Vec<String>
+    pub source_hashes: Vec<String>,
+    // Added after the fact, so older info.json files won't have it
+    #[serde(default)]
+    pub binaries:      Vec<Option<BinaryInfo>>,
+    #[serde(default)]
+    pub tags:          Vec<RunTags>,
+    #[serde(default)]
+    pub dates:         Vec<String>
+}
+
+// Runs with this tag are kept, but left out of analyses of the benchmark's history
+pub const EXCLUDED_TAG: &str = "excluded";
+
+impl IndividualBenchInfo {
+    pub fn is_excluded(&self, run: usize) -> bool {
+        self.tags.get(run).and_then(|tags| tags.get(EXCLUDED_TAG)).is_some_and(|value| value != "false")
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BinaryInfo {
+    pub size:     u64,
+    pub mtime:    u64,
+    pub sha256:   String,
+    pub build_id: Option<String>,
+    pub compiler: Option<String>,
+    pub flags:    Option<String>
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BenchHeader {
     // TODO: Make this a Path, not a string
-    pub source_root: String,
-    pub source_bin:  String,
-    pub description: String
+    pub source_root:   String,
+    pub source_bin:    String,
+    pub description:   String,
+    // Percentage changes within this are treated as noise, None for the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise_percent: Option<f64>
+}
+
+impl BenchHeader {
+    pub fn noise_band(&self) -> f64 {
+        self.noise_percent.unwrap_or(crate::compare::NOISE_PERCENT)
+    }
+}
+
+// Header fields supplied on the command line, anything left as None is unchanged
+pub struct HeaderEdits {
+    pub source_root:   Option<String>,
+    pub source_bin:    Option<String>,
+    pub description:   Option<String>,
+    pub noise_percent: Option<f64>
 }
 
 pub enum RunId {
     Index(usize),
+    // Half open, like a rust range
+    Range(usize, usize),
     All
 }
 
+pub type RunTags = BTreeMap<String, String>;
 pub type BenchId = String;
 pub type TopLevelBenchInfo = HashMap<BenchId, BenchHeader>;
-pub type BenchmarkQuery = Option<(BenchHeader, IndividualBenchInfo)>;
+pub type BenchmarkQuery = crate::error::Result<(BenchHeader, IndividualBenchInfo)>;
@@ -0,0 +1,294 @@
+// Copyright 2018 superfunc, see license.txt for usage details.
+//
+// Module containing the on disk layout of a workspace: a top.json cataloging
+// every benchmark, and an info.json per benchmark holding its recorded runs.
+//
+//   <root>/top.json
+//   <root>/<name>/info.json
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::types::{BenchHeader, BenchRunResult, BinaryInfo, IndividualBenchInfo, RunTags, TopLevelBenchInfo};
+
+// Directory name for project local workspaces, found like .git is
+pub const LOCAL_WORKSPACE_DIR: &str = ".benchviz";
+
+pub struct Workspace {
+    root: PathBuf
+}
+
+// Held for the duration of any change to the workspace, released on drop.
+pub struct WorkspaceLock {
+    _file: fs::File
+}
+
+impl Workspace {
+    pub fn open<P: Into<PathBuf>>(root: P) -> Workspace {
+        Workspace { root: root.into() }
+    }
+
+    // The workspace the CLI would use without --data-dir: $BENCHVIZ_HOME, a
+    // .benchviz directory in the current directory or any of its parents, then
+    // the global config directory.
+    pub fn locate() -> Option<Workspace> {
+        if let Some(home) = std::env::var_os("BENCHVIZ_HOME").filter(|home| !home.is_empty()) {
+            return Some(Workspace::open(home));
+        }
+
+        let cwd = std::env::current_dir().ok();
+        let local = cwd.as_ref().and_then(|cwd| cwd.ancestors().map(|dir| dir.join(LOCAL_WORKSPACE_DIR)).find(|dir| dir.is_dir()));
+        match local {
+            Some(local) => Some(Workspace::open(local)),
+            None => dirs::config_dir().map(|dir| Workspace::open(dir.join("bb/")))
+        }
+    }
+
+    // Create an empty workspace, including any missing parent directories
+    pub fn init<P: Into<PathBuf>>(root: P) -> Result<Workspace> {
+        let workspace = Workspace::open(root);
+        fs::create_dir_all(&workspace.root).map_err(Error::io(format!("Failed to create {:?}", workspace.root)))?;
+        if !workspace.top_level_file().exists() {
+            workspace.write_headers(&TopLevelBenchInfo::new())?;
+        }
+
+        Ok(workspace)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn top_level_file(&self) -> PathBuf {
+        self.root.join("top.json")
+    }
+
+    pub fn benchmark_dir(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    pub fn individual_file(&self, name: &str) -> PathBuf {
+        self.benchmark_dir(name).join("info.json")
+    }
+
+    pub fn headers(&self) -> Result<TopLevelBenchInfo> {
+        read_json(&self.top_level_file())
+    }
+
+    pub fn header(&self, name: &str) -> Result<BenchHeader> {
+        let mut benches = self.headers()?;
+        match benches.remove(name) {
+            Some(header) => Ok(header),
+            None => Err(Error::unknown_benchmark(name, &benches))
+        }
+    }
+
+    pub fn runs(&self, name: &str) -> Result<IndividualBenchInfo> {
+        let mut info: IndividualBenchInfo = read_json(&self.individual_file(name))?;
+        let num_runs = info.benchmarks.len();
+        if num_runs != info.commentary.len() || num_runs != info.source_hashes.len() {
+            let message = "inconsistent number of runs, perhaps it was hand edited?";
+            return Err(Error::parse(format!("{:?}", self.individual_file(name)), message));
+        }
+
+        // Runs recorded before these were tracked have nothing to show for them
+        info.binaries.resize(info.benchmarks.len(), None);
+        info.tags.resize(info.benchmarks.len(), RunTags::new());
+        info.dates.resize(info.benchmarks.len(), String::new());
+        Ok(info)
+    }
+
+    pub fn write_headers(&self, benches: &TopLevelBenchInfo) -> Result<()> {
+        write_json(&self.top_level_file(), benches)
+    }
+
+    pub fn write_runs(&self, name: &str, info: &IndividualBenchInfo) -> Result<()> {
+        write_json(&self.individual_file(name), info)
+    }
+
+    // Take an advisory lock on the workspace, blocking until any other holder
+    // is done. Anything changing the workspace must hold it, and re-read what it
+    // modifies once locked; the lock is released when dropped.
+    pub fn lock(&self) -> Result<WorkspaceLock> {
+        let file = self.lock_file()?;
+        file.lock().map_err(Error::io(format!("Failed to lock {:?}", self.root)))?;
+        Ok(WorkspaceLock { _file: file })
+    }
+
+    // As lock, but None rather than waiting if someone else holds it
+    pub fn try_lock(&self) -> Result<Option<WorkspaceLock>> {
+        let file = self.lock_file()?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(WorkspaceLock { _file: file })),
+            Err(fs::TryLockError::WouldBlock) => Ok(None),
+            Err(fs::TryLockError::Error(e)) => Err(Error::io(format!("Failed to lock {:?}", self.root))(e))
+        }
+    }
+
+    fn lock_file(&self) -> Result<fs::File> {
+        let path = self.root.join(".lock");
+        let file = fs::OpenOptions::new().create(true).truncate(false).write(true).open(&path);
+        file.map_err(Error::io(format!("Failed to open lock file {:?}", path)))
+    }
+
+    // Register a new benchmark with no runs
+    pub fn add_benchmark(&self, lock: &WorkspaceLock, name: &str, header: BenchHeader) -> Result<()> {
+        if self.add_benchmarks(lock, vec![(name.to_string(), header)])?[0] {
+            Ok(())
+        } else {
+            Err(Error::Invalid(format!("Name {:?} already exists in benchmarks.", name)))
+        }
+    }
+
+    // Register several new benchmarks with no runs, writing the top level file once.
+    // Names already taken (including earlier in the list) are skipped; the result says
+    // which of them were registered.
+    pub fn add_benchmarks(&self, _lock: &WorkspaceLock, new: Vec<(String, BenchHeader)>) -> Result<Vec<bool>> {
+        let mut benches = self.headers()?;
+        let mut added = Vec::new();
+        for (name, header) in new {
+            let is_new = !benches.contains_key(&name);
+            added.push(is_new);
+            if !is_new {
+                continue;
+            }
+
+            let dir = self.benchmark_dir(&name);
+            fs::create_dir(&dir).map_err(Error::io(format!("Failed to create {:?}", dir)))?;
+            self.write_runs(&name, &IndividualBenchInfo::default())?;
+            benches.insert(name, header);
+        }
+
+        self.write_headers(&benches)?;
+        Ok(added)
+    }
+
+    // Record a new run of a benchmark, returning its index
+    pub fn append_run(
+        &self,
+        _lock: &WorkspaceLock,
+        name: &str,
+        result: BenchRunResult,
+        commentary: &str,
+        tags: &RunTags,
+        binary: Option<BinaryInfo>
+    ) -> Result<usize>
+    {
+        // Runs from outside of a git checkout are still worth recording, they
+        // just won't have any source diffs.
+        let source_hash = crate::git::hash(&self.header(name)?.source_root).unwrap_or_default();
+        let mut info = self.runs(name)?;
+        info.dates.push(result.context.map_or(String::new(), |context| context.date));
+        info.benchmarks.push(result.benchmarks);
+        info.commentary.push(commentary.to_string());
+        info.source_hashes.push(source_hash);
+        info.binaries.push(binary);
+        info.tags.push(tags.clone());
+        self.write_runs(name, &info)?;
+        Ok(info.benchmarks.len() - 1)
+    }
+
+    pub fn compare(
+        &self,
+        name: &str,
+        lhs: usize,
+        rhs: usize,
+        baseline: crate::compare::Baseline,
+        metrics: &[crate::metric::Metric]
+    ) -> Result<crate::compare::Comparison>
+    {
+        let info = self.runs(name)?;
+        match crate::compare::compare_runs(&info, lhs, rhs, baseline, metrics) {
+            Some(comparison) => Ok(comparison),
+            None => Err(Error::InvalidRunId(format!(
+                "Invalid run id specified ({}), only {} runs recorded",
+                std::cmp::max(lhs, rhs),
+                info.benchmarks.len()
+            )))
+        }
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Err(Error::ConfigMissing(path.to_owned())),
+        Err(e) => return Err(Error::io(format!("Failed to read {:?}", path))(e))
+    };
+
+    serde_json::from_slice(&contents).map_err(|e| Error::parse(format!("{:?}", path), e))
+}
+
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<()> {
+    let contents = serde_json::to_string_pretty(value).map_err(|e| Error::parse(format!("{:?}", path), e))?;
+    write_atomically(path, &contents).map_err(Error::io(format!("Failed to write config file {:?}", path)))
+}
+
+// Files are replaced rather than overwritten: the new contents go to a temp file
+// which is synced and then renamed over the old one, so a crash at any point
+// leaves either the old or new file intact. The previous version is kept
+// alongside as <file>.bak.
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+
+    if path.is_file() {
+        fs::copy(path, path.with_extension("json.bak"))?;
+    }
+
+    fs::rename(&tmp, path)?;
+
+    // Make sure the rename itself is durable, where the platform allows it
+    if let Some(dir) = path.parent().and_then(|parent| fs::File::open(parent).ok()) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::Baseline;
+    use crate::metric::Metric;
+    use crate::error::Error;
+    use crate::types::{BenchHeader, BenchResult, BenchRunResult, RunTags};
+    use crate::workspace::Workspace;
+
+    #[test]
+    fn check_round_trip() {
+        let root = std::env::temp_dir().join(format!("benchviz-test-{}", std::process::id()));
+        let workspace = Workspace::init(&root).unwrap();
+        let header = BenchHeader {
+            source_root:   ".".to_string(),
+            source_bin:    "bench".to_string(),
+            description:   "".to_string(),
+            noise_percent: None
+        };
+        let lock = workspace.lock().unwrap();
+        workspace.add_benchmark(&lock, "example", header).unwrap();
+        assert!(workspace.add_benchmark(&lock, "example", workspace.header("example").unwrap()).is_err());
+        let batch = ["example", "other", "other"].iter().map(|name| (name.to_string(), workspace.header("example").unwrap()));
+        assert_eq!(workspace.add_benchmarks(&lock, batch.collect()).unwrap(), vec![false, true, false]);
+        assert_eq!(workspace.headers().unwrap().len(), 2);
+        assert!(matches!(workspace.header("exmaple"), Err(Error::UnknownBenchmark { ref suggestions, .. }) if suggestions == &["example"]));
+
+        let result = |time: f64| BenchRunResult {
+            context:    None,
+            benchmarks: vec![BenchResult::timed("BM_Example", time)]
+        };
+        assert_eq!(workspace.append_run(&lock, "example", result(2.0), "first", &RunTags::new(), None).unwrap(), 0);
+        assert_eq!(workspace.append_run(&lock, "example", result(1.0), "second", &RunTags::new(), None).unwrap(), 1);
+
+        let comparison = workspace.compare("example", 0, 1, Baseline::Lhs, &[Metric::RealTime]).unwrap();
+        assert_eq!(comparison.rows.len(), 1);
+        assert_eq!(comparison.rows[0].speedup, 2.0);
+        assert!(matches!(workspace.compare("example", 0, 2, Baseline::Lhs, &[Metric::RealTime]), Err(Error::InvalidRunId(_))));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}